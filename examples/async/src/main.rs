@@ -0,0 +1,93 @@
+use std::fs;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::Duration;
+use wren_rs::{Configuration, VM};
+
+// Completes after `duration`, counted by a thread that wakes the task once
+// it is done.
+struct Delay {
+    duration: Duration,
+    done: Arc<AtomicBool>,
+    waker: Option<Arc<Mutex<Waker>>>,
+}
+
+impl Delay {
+    fn new(duration: Duration) -> Delay {
+        Delay {
+            duration,
+            done: Arc::new(AtomicBool::new(false)),
+            waker: None,
+        }
+    }
+}
+
+impl Future for Delay {
+    type Output = ();
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.done.load(Ordering::SeqCst) {
+            return Poll::Ready(());
+        }
+        match &self.waker {
+            Some(waker) => *waker.lock().unwrap() = cx.waker().clone(),
+            None => {
+                let waker = Arc::new(Mutex::new(cx.waker().clone()));
+                let (done, duration) = (self.done.clone(), self.duration);
+                let wake = waker.clone();
+                thread::spawn(move || {
+                    thread::sleep(duration);
+                    done.store(true, Ordering::SeqCst);
+                    wake.lock().unwrap().wake_by_ref();
+                });
+                self.waker = Some(waker);
+            }
+        }
+        Poll::Pending
+    }
+}
+
+fn main() {
+    let mut config = Configuration::new();
+    config.capture_output();
+    config.register_async_method("main", "Timer", true, "sleep_(_,_)", |vm| {
+        let ms = vm.get::<u64>(2);
+        async move {
+            let ms = ms.map_err(|e| e.to_string())?;
+            Delay::new(Duration::from_millis(ms)).await;
+            Ok(ms)
+        }
+    });
+    config.register_async_method("main", "Timer", true, "fail_(_,_)", |vm| {
+        let message = vm.get::<String>(2);
+        async move {
+            let message = message.map_err(|e| e.to_string())?;
+            Delay::new(Duration::from_millis(1)).await;
+            Err::<(), _>(message)
+        }
+    });
+    let mut vm = VM::new(config);
+
+    // The script stops at the first sleep and waits for the host.
+    let source = fs::read("./src/async.wren").unwrap();
+    vm.interpret("main", source).unwrap();
+    assert_eq!(vm.take_output(), "sleeping\n");
+    assert_eq!(vm.pending_tasks(), 1);
+
+    // Nothing is ready yet, so polling leaves the fiber where it is.
+    let waker = Waker::noop();
+    let mut cx = Context::from_waker(waker);
+    assert!(vm.poll(&mut cx).is_pending());
+    assert_eq!(vm.take_output(), "");
+
+    vm.run_until_idle().unwrap();
+    assert_eq!(vm.pending_tasks(), 0);
+    assert_eq!(vm.take_output(), "slept 20 ms\ndisk on fire\nslept 5 ms\n");
+
+    // An error nobody catches ends the script like any runtime error.
+    vm.interpret("main", "Timer.fail(\"gone\")").unwrap();
+    assert!(vm.run_until_idle().is_err());
+}
@@ -0,0 +1,54 @@
+use std::fs;
+use wren_rs::{Configuration, Fiber, Value, VM};
+
+fn main() {
+    let mut vm = VM::new(Configuration::new());
+    let source = fs::read("./src/fiber.wren").unwrap();
+    vm.interpret("main", source).unwrap();
+
+    vm.ensure_slots(1);
+    vm.get_variable("main", "Counter", 0).unwrap();
+    let counter = vm.get_slot_handle(0);
+    let fiber = vm.new_fiber(&counter).unwrap();
+
+    // The first value starts the function, later ones come out of
+    // Fiber.yield().
+    assert_eq!(fiber.call::<f64, _>(&mut vm, (10,)).unwrap(), 10.0);
+    assert_eq!(fiber.call::<f64, _>(&mut vm, (5,)).unwrap(), 15.0);
+    assert_eq!(fiber.call::<f64, _>(&mut vm, (-3,)).unwrap(), 12.0);
+    assert!(!fiber.is_done(&mut vm).unwrap());
+
+    vm.get_variable("main", "Failing", 0).unwrap();
+    let failing = vm.get_slot_handle(0);
+    let fiber = vm.new_fiber(&failing).unwrap();
+    let error = fiber.try_call(&mut vm, ()).unwrap().unwrap_err();
+    assert_eq!(
+        error,
+        Value::Map(vec![(Value::String(b"code".to_vec()), Value::Num(42.0))])
+    );
+    assert!(fiber.is_done(&mut vm).unwrap());
+    assert_eq!(fiber.error(&mut vm).unwrap(), Some(error));
+
+    // Fibers made by the script work the same way.
+    vm.get_variable("main", "Generator", 0).unwrap();
+    let generator: Fiber = vm.get(0).unwrap();
+    assert_eq!(generator.call::<String, _>(&mut vm, ()).unwrap(), "first");
+    assert_eq!(generator.call::<String, _>(&mut vm, ()).unwrap(), "second");
+    generator.call::<(), _>(&mut vm, ()).unwrap();
+    assert!(generator.is_done(&mut vm).unwrap());
+    assert!(generator.call::<(), _>(&mut vm, ()).is_err());
+
+    // Generators can be consumed as iterators.
+    vm.get_variable("main", "Squares", 0).unwrap();
+    let squares = vm.get_slot_handle(0);
+    let items: Result<Vec<u32>, _> = vm.fiber_iter(&squares).collect();
+    assert_eq!(items.unwrap(), [1, 4, 9, 16]);
+    assert_eq!(vm.fiber_iter::<u32>(&squares).count(), 0);
+
+    vm.get_variable("main", "Broken", 0).unwrap();
+    let broken: Fiber = vm.get(0).unwrap();
+    let mut items = broken.iter::<f64>(&mut vm);
+    assert_eq!(items.next().unwrap().unwrap(), 1.0);
+    assert!(items.next().unwrap().is_err());
+    assert!(items.next().is_none());
+}
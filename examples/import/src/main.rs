@@ -0,0 +1,13 @@
+use wren_rs::{ChainLoader, Configuration, FileLoader, MemoryLoader, VM};
+
+fn main() {
+    let mut memory = MemoryLoader::new();
+    memory.insert("version", "var Version = \"1.0\"\n");
+
+    let mut config = Configuration::new();
+    config.set_module_loader(ChainLoader::new().with(memory).with(FileLoader::new()));
+    let mut vm = VM::new(config);
+    if let Err(error) = vm.read_file("./src/import.wren") {
+        panic!("{}", error);
+    }
+}
@@ -0,0 +1,48 @@
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+use wren_rs::{Configuration, VM};
+
+// Hands out the writes it receives, so they can be inspected after the VM
+// is done with the sink.
+#[derive(Clone, Default)]
+struct Sink(Rc<RefCell<Vec<String>>>);
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .borrow_mut()
+            .push(String::from_utf8_lossy(buf).into_owned());
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn main() {
+    let mut config = Configuration::new();
+    config.capture_output();
+    let mut vm = VM::new(config);
+    vm.interpret("main", "System.print(\"hello\")\nSystem.write(1 + 2)")
+        .unwrap();
+    assert_eq!(vm.take_output(), "hello\n3");
+    assert_eq!(vm.take_output(), "");
+
+    // System.print writes the text and the newline separately, the line
+    // writer hands them on together.
+    let sink = Sink::default();
+    let mut config = Configuration::new();
+    config.set_line_writer(sink.clone());
+    let mut vm = VM::new(config);
+    vm.interpret("main", "System.print(\"one\")\nSystem.write(\"two\")")
+        .unwrap();
+    assert_eq!(*sink.0.borrow(), ["one\n"]);
+    drop(vm);
+    assert_eq!(*sink.0.borrow(), ["one\n", "two"]);
+
+    let mut config = Configuration::new();
+    config.set_writer(io::stderr());
+    let mut vm = VM::new(config);
+    vm.interpret("main", "System.print(\"to stderr\")").unwrap();
+}
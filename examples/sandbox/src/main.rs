@@ -0,0 +1,85 @@
+use std::thread;
+use std::time::Duration;
+use wren_rs::{Configuration, Error, VM};
+
+fn main() {
+    let mut config = Configuration::new();
+    config.set_memory_limit(4 * 1024 * 1024);
+    // Collect early and often to keep pauses short.
+    config.set_initial_heap_size(256 * 1024);
+    config.set_min_heap_size(64 * 1024);
+    config.set_heap_growth_percent(25);
+    let mut vm = VM::new(config);
+
+    let before = vm.memory_stats();
+    assert_eq!(before.limit, Some(4 * 1024 * 1024));
+    assert!(before.current > 0 && before.current <= before.peak);
+
+    // Garbage on its own is fine, it gets collected before the limit counts.
+    vm.interpret(
+        "main",
+        "for (i in 0...100000) {
+  var garbage = [i, i.toString]
+}",
+    )
+    .unwrap();
+
+    let result = vm.interpret(
+        "main",
+        "var hoard = []
+while (true) hoard.add(\"more\" * 100)",
+    );
+    match result {
+        Err(Error::Runtime { message, .. }) => assert_eq!(message, "Out of memory."),
+        _ => panic!("the script should have run out of memory"),
+    }
+    let stats = vm.memory_stats();
+    assert!(stats.peak <= 4 * 1024 * 1024);
+    println!("{:?}", stats);
+
+    // A single allocation is refused before it happens, however big.
+    let result = vm.interpret("main", "var big = List.filled(100000000, 0)");
+    assert!(matches!(result, Err(Error::Runtime { .. })));
+    assert!(vm.memory_stats().peak <= 4 * 1024 * 1024);
+
+    // The VM is still usable once the hoard is unreachable again.
+    vm.interpret("main", "hoard = null").unwrap();
+    let gc = vm.collect_garbage();
+    assert!(gc.after < 4 * 1024 * 1024 && gc.freed() > 0);
+    println!("{:?}", gc);
+
+    // Runaway scripts are stopped by the budget, even when they try to catch
+    // the error.
+    vm.set_time_limit(Some(Duration::from_millis(100)));
+    let error = vm
+        .interpret(
+            "main",
+            "while (true) {
+  Fiber.new { while (true) {} }.try()
+}",
+        )
+        .unwrap_err();
+    assert_eq!(
+        error.to_string().lines().next(),
+        Some("Execution interrupted: time limit exceeded.")
+    );
+    vm.set_time_limit(None);
+
+    vm.set_step_limit(Some(1000));
+    assert!(vm.interpret("main", "for (i in 0...10) {}").is_ok());
+    assert!(vm.interpret("main", "for (i in 0...10000) {}").is_err());
+    vm.set_step_limit(None);
+
+    let handle = vm.interrupt_handle();
+    let interrupter = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        handle.interrupt();
+    });
+    let error = vm.interpret("main", "while (true) {}").unwrap_err();
+    assert!(error.to_string().starts_with("Execution interrupted."));
+    interrupter.join().unwrap();
+
+    // The VM carries on as normal afterwards.
+    vm.interpret("main", "System.print(\"still here\")")
+        .unwrap();
+}
@@ -0,0 +1,92 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use wren_rs::{Configuration, Error, VM};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Config {
+    name: String,
+    servers: Vec<Server>,
+    mode: Mode,
+    limit: Limit,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Server {
+    host: String,
+    port: u16,
+    backup: Option<bool>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Mode {
+    Fast,
+    Safe,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Limit {
+    None,
+    Bytes(u64),
+    Timeout { seconds: u32 },
+}
+
+fn main() {
+    let mut vm = VM::new(Configuration::new());
+    let source = fs::read("./src/serde.wren").unwrap();
+    vm.interpret("main", source).unwrap();
+
+    vm.ensure_slots(2);
+    vm.get_variable("main", "Config", 0).unwrap();
+    let class = vm.get_slot_handle(0);
+    let describe = vm.make_call_handle("describe(_)").unwrap();
+    let build = vm.make_call_handle("build()").unwrap();
+    let broken = vm.make_call_handle("broken()").unwrap();
+
+    let example = Config {
+        name: "example".to_string(),
+        servers: vec![
+            Server {
+                host: "localhost".to_string(),
+                port: 8080,
+                backup: Some(false),
+            },
+            Server {
+                host: "localhost".to_string(),
+                port: 8081,
+                backup: None,
+            },
+        ],
+        mode: Mode::Fast,
+        limit: Limit::Bytes(1024),
+    };
+    vm.set_slot_handle(0, &class);
+    vm.set_serialize(1, &example).unwrap();
+    vm.call(&describe).unwrap();
+
+    vm.set_slot_handle(0, &class);
+    vm.call(&build).unwrap();
+    let built: Config = vm.get_deserialize(0).unwrap();
+    assert_eq!(
+        built,
+        Config {
+            name: "from wren".to_string(),
+            servers: vec![Server {
+                host: "localhost".to_string(),
+                port: 80,
+                backup: Some(true),
+            }],
+            mode: Mode::Safe,
+            limit: Limit::Timeout { seconds: 5 },
+        }
+    );
+
+    vm.set_slot_handle(0, &class);
+    vm.call(&broken).unwrap();
+    match vm.get_deserialize::<Config>(0) {
+        Err(Error::Serde { path, message }) => {
+            assert_eq!(path, "servers[1].port");
+            println!("{}: {}", path, message);
+        }
+        other => panic!("expected a serde error, got {:?}", other),
+    }
+}
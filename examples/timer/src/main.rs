@@ -0,0 +1,52 @@
+use std::fs;
+use std::time::{Duration, Instant};
+use wren_rs::{Configuration, VM};
+
+fn main() {
+    let mut config = Configuration::new();
+    config.capture_output();
+    config.enable_scheduler();
+    config.use_virtual_clock();
+    let mut vm = VM::new(config);
+
+    // Every fiber is asleep when the script hands control back.
+    let source = fs::read("./src/timer.wren").unwrap();
+    vm.interpret("main", source).unwrap();
+    assert_eq!(vm.take_output(), "main sleeps\na starts\n");
+    assert_eq!(vm.pending_timers(), 3);
+    assert_eq!(vm.next_timer(), Some(Duration::from_millis(10)));
+
+    // The virtual clock stands still until it is told to move.
+    assert_eq!(vm.fire_timers().unwrap(), 0);
+    assert_eq!(vm.advance_clock(Duration::from_millis(5)).unwrap(), 0);
+    assert_eq!(vm.next_timer(), Some(Duration::from_millis(5)));
+
+    assert_eq!(vm.advance_clock(Duration::from_millis(15)).unwrap(), 2);
+    assert_eq!(vm.take_output(), "a after 10\nmain after 20\n");
+
+    vm.run_timers().unwrap();
+    assert_eq!(vm.take_output(), "b after 30\n");
+    assert_eq!(vm.pending_timers(), 0);
+    assert_eq!(vm.next_timer(), None);
+
+    let error = vm
+        .interpret("main", "Timer.sleep(-1)")
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("Milliseconds cannot be negative."));
+
+    // With the real clock the loop waits for the sleep to be over.
+    let mut config = Configuration::new();
+    config.capture_output();
+    config.enable_scheduler();
+    let mut vm = VM::new(config);
+    let start = Instant::now();
+    vm.interpret(
+        "main",
+        "import \"timer\" for Timer\nSystem.print(Timer.sleep(5))",
+    )
+    .unwrap();
+    vm.run_timers().unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(5));
+    assert_eq!(vm.take_output(), "null\n");
+}
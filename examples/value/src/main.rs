@@ -0,0 +1,86 @@
+use std::collections::{BTreeMap, HashMap};
+use wren_rs::{Configuration, Error, Value, VM};
+
+fn main() {
+    let mut vm = VM::new(Configuration::new());
+    vm.interpret(
+        "main",
+        "var Data = [1, \"two\", null, {\"three\": [true]}, 1..2]
+var Cyclic = [1]
+Cyclic.add(Cyclic)
+var Scores = {\"ann\": 3, \"bob\": 5, \"cid\": 4}",
+    )
+    .unwrap();
+
+    vm.ensure_slots(1);
+    vm.get_variable("main", "Data", 0).unwrap();
+    let data = vm.get_value(0).unwrap();
+    println!("{}", data); // [1, two, null, {three: [true]}, instance]
+    match &data {
+        Value::List(list) => {
+            assert_eq!(list[0], Value::Num(1.0));
+            assert_eq!(list[1], Value::String(b"two".to_vec()));
+            assert!(matches!(list[4], Value::Object(_)));
+        }
+        _ => panic!("Data should be a list"),
+    }
+
+    // A copy written back and read again compares equal, the range included
+    // since it's the very same object.
+    vm.set_value(0, &data).unwrap();
+    assert_eq!(vm.get_value(0).unwrap(), data);
+
+    let map = Value::Map(vec![
+        (Value::String(b"a".to_vec()), Value::Num(1.0)),
+        (Value::Num(2.0), Value::List(vec![Value::Null])),
+    ]);
+    vm.set_value(0, &map).unwrap();
+    assert_eq!(vm.get_value(0).unwrap(), map);
+
+    vm.get_variable("main", "Cyclic", 0).unwrap();
+    assert!(matches!(vm.get_value(0), Err(Error::Cycle)));
+
+    // Maps can be read without knowing their keys up front.
+    vm.get_variable("main", "Scores", 0).unwrap();
+    let mut entries: Vec<(String, f64)> = vm.map_entries(0).unwrap();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(entries[1], ("bob".to_string(), 5.0));
+
+    let scores: BTreeMap<String, u32> = vm.get(0).unwrap();
+    assert_eq!(scores.keys().collect::<Vec<_>>(), ["ann", "bob", "cid"]);
+    let scores: HashMap<String, u32> = vm.get(0).unwrap();
+    assert_eq!(scores["cid"], 4);
+    vm.set(0, &scores);
+    assert_eq!(vm.get_map_count(0), 3);
+
+    vm.ensure_slots(2);
+    vm.set_slot_list_from_iter(0, ["a", "b", "c", "d"]);
+    vm.get_list_element(0, -1, 1).unwrap();
+    assert_eq!(vm.get::<String>(1).unwrap(), "d");
+    assert!(matches!(
+        vm.get_list_element(0, 4, 1),
+        Err(Error::Index { index: 4, count: 4 })
+    ));
+    vm.remove_from_list(0, 1, 1).unwrap();
+    assert_eq!(vm.get::<String>(1).unwrap(), "b");
+    let letters: Vec<String> = vm
+        .list_iter(0)
+        .unwrap()
+        .map(|slot| vm.get(slot).unwrap())
+        .collect();
+    assert_eq!(letters, ["a", "c", "d"]);
+    vm.clear_list(0).unwrap();
+    assert_eq!(vm.get_list_count(0), 0);
+
+    // Strings are bytes to wren, NULs and invalid UTF-8 included.
+    vm.set_slot_string(0, "nul\0inside");
+    assert_eq!(vm.get_slot_string(0).unwrap(), b"nul\0inside");
+    vm.set_slot_bytes(0, b"\xff\xfe");
+    assert!(matches!(vm.get_slot_str(0), Err(Error::Utf8(_))));
+    assert_eq!(vm.get_slot_bytes(0), Some(&b"\xff\xfe"[..]));
+    assert!(matches!(
+        vm.get_variable("main", "Da\0ta", 0),
+        Err(Error::Nul(_))
+    ));
+    assert!(!vm.has_module("ma\0in"));
+}
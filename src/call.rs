@@ -0,0 +1,108 @@
+use crate::vm::Handle;
+use crate::{Error, FromWren, ToWren, VM};
+
+pub trait Args {
+    const ARITY: usize;
+    fn push(&self, vm: &mut VM, first_slot: i32);
+}
+
+macro_rules! tuple_args {
+    ($arity:expr; $($name:ident $index:tt),*) => {
+        impl<$($name: ToWren),*> Args for ($($name,)*) {
+            const ARITY: usize = $arity;
+            #[allow(unused_variables)]
+            fn push(&self, vm: &mut VM, first_slot: i32) {
+                $(self.$index.to_wren(vm, first_slot + $index);)*
+            }
+        }
+    };
+}
+
+tuple_args!(0;);
+tuple_args!(1; A 0);
+tuple_args!(2; A 0, B 1);
+tuple_args!(3; A 0, B 1, C 2);
+tuple_args!(4; A 0, B 1, C 2, D 3);
+tuple_args!(5; A 0, B 1, C 2, D 3, E 4);
+tuple_args!(6; A 0, B 1, C 2, D 3, E 4, F 5);
+tuple_args!(7; A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+tuple_args!(8; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+
+impl<'a> FromWren<'a> for () {
+    fn from_wren(_: &'a mut VM, _: i32) -> Result<Self, Error> {
+        Ok(())
+    }
+}
+
+impl ToWren for () {
+    fn to_wren(&self, vm: &mut VM, slot: i32) {
+        vm.set_slot_null(slot)
+    }
+}
+
+// A bare method name gets its parameter list from the arity of the arguments,
+// anything else is taken as a full signature and checked against it.
+fn full_signature(signature: &str, arity: usize) -> Result<String, Error> {
+    let is_name = signature
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if is_name && arity > 0 {
+        let params = vec!["_"; arity].join(",");
+        return Ok(format!("{}({})", signature, params));
+    }
+    let params = match signature.find(['(', '[']) {
+        Some(start) => signature[start..].matches('_').count(),
+        None => 0,
+    };
+    if params != arity {
+        return Err(Error::Arity {
+            signature: signature.to_string(),
+            expected: params,
+            found: arity,
+        });
+    }
+    Ok(signature.to_string())
+}
+
+impl VM {
+    /// A method that suspends its fiber, with `Fiber.suspend()` or by waiting
+    /// on something, returns null.
+    pub fn call_method<'a, R: FromWren<'a>, A: Args>(
+        &'a mut self,
+        receiver: &Handle,
+        signature: &str,
+        args: A,
+    ) -> Result<R, Error> {
+        if !self.send(receiver, signature, args)? {
+            self.ensure_slots(1);
+            self.set_slot_null(0);
+        }
+        R::from_wren(self, 0)
+    }
+    // Leaves what the method returned in slot 0. False when there is nothing
+    // there, wrenCall() leaves no slots behind when the fiber was suspended.
+    pub(crate) fn send<A: Args>(
+        &mut self,
+        receiver: &Handle,
+        signature: &str,
+        args: A,
+    ) -> Result<bool, Error> {
+        if self.in_foreign_method() {
+            return Err(Error::InForeignMethod);
+        }
+        self.check_handle(receiver)?;
+        let signature = full_signature(signature, A::ARITY)?;
+        let method = match self.data().call_handles.remove(&signature) {
+            Some(method) => method,
+            None => self.make_call_handle(&signature)?,
+        };
+
+        self.ensure_slots(A::ARITY as i32 + 1);
+        self.set_slot_handle(0, receiver);
+        args.push(self, 1);
+        let result = self.call(&method);
+        self.data().call_handles.insert(signature, method);
+        result?;
+        Ok(self.get_slot_count() > 0)
+    }
+}
@@ -0,0 +1,305 @@
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{BuildHasher, Hash};
+use std::{slice, str};
+
+use crate::ffi;
+use crate::vm::Handle;
+use crate::{Error, Type, VM};
+
+pub trait FromWren<'a>: Sized {
+    fn from_wren(vm: &'a mut VM, slot: i32) -> Result<Self, Error>;
+}
+
+pub trait ToWren {
+    fn to_wren(&self, vm: &mut VM, slot: i32);
+}
+
+impl VM {
+    pub fn get<'a, T: FromWren<'a>>(&'a mut self, slot: i32) -> Result<T, Error> {
+        self.check_slot(slot)?;
+        T::from_wren(self, slot)
+    }
+    /// Adds slots up to `slot` when there aren't enough. Panics on a negative
+    /// slot.
+    pub fn set<T: ToWren>(&mut self, slot: i32, value: T) {
+        assert!(slot >= 0, "Slot {} is out of bounds", slot);
+        if slot >= self.get_slot_count() {
+            self.ensure_slots(slot + 1);
+        }
+        value.to_wren(self, slot)
+    }
+    pub(crate) fn expect_type(&mut self, slot: i32, expected: Type) -> Result<(), Error> {
+        let found = self.get_slot_type(slot);
+        if found == expected {
+            Ok(())
+        } else {
+            Err(Error::Type { expected, found })
+        }
+    }
+    // Hands out `n` unused slots past the ones currently in use. They stay
+    // until the enclosing `restoring_slots` returns.
+    pub(crate) fn scratch_slots(&mut self, n: i32) -> i32 {
+        let base = self.get_slot_count();
+        self.ensure_slots(base + n);
+        base
+    }
+    // Runs `f` and drops whatever slots it added, so the slot count seen by
+    // the caller doesn't change.
+    pub(crate) fn restoring_slots<R, F: FnOnce(&mut VM) -> R>(&mut self, f: F) -> R {
+        let count = self.get_slot_count();
+        let result = f(self);
+        unsafe { ffi::wrenTruncateSlots(self.raw, count) };
+        result
+    }
+}
+
+impl<T: ToWren + ?Sized> ToWren for &T {
+    fn to_wren(&self, vm: &mut VM, slot: i32) {
+        (**self).to_wren(vm, slot)
+    }
+}
+
+impl<'a> FromWren<'a> for bool {
+    fn from_wren(vm: &'a mut VM, slot: i32) -> Result<Self, Error> {
+        vm.expect_type(slot, Type::Bool)?;
+        Ok(vm.get_slot_bool(slot).unwrap())
+    }
+}
+
+impl ToWren for bool {
+    fn to_wren(&self, vm: &mut VM, slot: i32) {
+        vm.set_slot_bool(slot, *self)
+    }
+}
+
+impl<'a> FromWren<'a> for f64 {
+    fn from_wren(vm: &'a mut VM, slot: i32) -> Result<Self, Error> {
+        vm.expect_type(slot, Type::Num)?;
+        Ok(vm.get_slot_double(slot).unwrap())
+    }
+}
+
+impl<'a> FromWren<'a> for f32 {
+    fn from_wren(vm: &'a mut VM, slot: i32) -> Result<Self, Error> {
+        Ok(f64::from_wren(vm, slot)? as f32)
+    }
+}
+
+macro_rules! int_from_wren {
+    ($($t:ty),*) => {
+        $(
+            impl<'a> FromWren<'a> for $t {
+                fn from_wren(vm: &'a mut VM, slot: i32) -> Result<Self, Error> {
+                    let value = f64::from_wren(vm, slot)?;
+                    // MAX + 1 is exact in f64 even where MAX itself is not.
+                    if value.fract() == 0.0
+                        && value >= <$t>::MIN as f64
+                        && value < <$t>::MAX as f64 + 1.0
+                    {
+                        Ok(value as $t)
+                    } else {
+                        Err(Error::Num {
+                            value,
+                            target: stringify!($t),
+                        })
+                    }
+                }
+            }
+        )*
+    };
+}
+
+int_from_wren!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+macro_rules! num_to_wren {
+    ($($t:ty),*) => {
+        $(
+            impl ToWren for $t {
+                fn to_wren(&self, vm: &mut VM, slot: i32) {
+                    vm.set_slot_double(slot, *self as f64)
+                }
+            }
+        )*
+    };
+}
+
+num_to_wren!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize, f32, f64);
+
+fn slot_bytes(vm: &mut VM, slot: i32) -> Result<&[u8], Error> {
+    vm.expect_type(slot, Type::String)?;
+    let mut len = 0;
+    let ptr = unsafe { ffi::wrenGetSlotBytes(vm.raw, slot, &mut len) };
+    Ok(unsafe { slice::from_raw_parts(ptr as *const u8, len as usize) })
+}
+
+impl<'a> FromWren<'a> for &'a str {
+    fn from_wren(vm: &'a mut VM, slot: i32) -> Result<Self, Error> {
+        Ok(str::from_utf8(slot_bytes(vm, slot)?)?)
+    }
+}
+
+impl<'a> FromWren<'a> for String {
+    fn from_wren(vm: &'a mut VM, slot: i32) -> Result<Self, Error> {
+        Ok(str::from_utf8(slot_bytes(vm, slot)?)?.to_string())
+    }
+}
+
+impl ToWren for str {
+    fn to_wren(&self, vm: &mut VM, slot: i32) {
+        vm.set_slot_bytes(slot, self.as_bytes())
+    }
+}
+
+impl ToWren for String {
+    fn to_wren(&self, vm: &mut VM, slot: i32) {
+        self.as_str().to_wren(vm, slot)
+    }
+}
+
+impl<'a> FromWren<'a> for Handle {
+    fn from_wren(vm: &'a mut VM, slot: i32) -> Result<Self, Error> {
+        Ok(vm.get_slot_handle(slot))
+    }
+}
+
+impl ToWren for Handle {
+    fn to_wren(&self, vm: &mut VM, slot: i32) {
+        vm.set_slot_handle(slot, self)
+    }
+}
+
+impl<'a, T: FromWren<'a>> FromWren<'a> for Option<T> {
+    fn from_wren(vm: &'a mut VM, slot: i32) -> Result<Self, Error> {
+        if vm.get_slot_type(slot) == Type::Null {
+            Ok(None)
+        } else {
+            T::from_wren(vm, slot).map(Some)
+        }
+    }
+}
+
+impl<T: ToWren> ToWren for Option<T> {
+    fn to_wren(&self, vm: &mut VM, slot: i32) {
+        match self {
+            Some(value) => value.to_wren(vm, slot),
+            None => vm.set_slot_null(slot),
+        }
+    }
+}
+
+// `Vec<u8>` maps to a byte string rather than a list of numbers, but a list of
+// numbers is still accepted when reading one.
+impl<'a, T: for<'b> FromWren<'b> + 'static> FromWren<'a> for Vec<T> {
+    fn from_wren(vm: &'a mut VM, slot: i32) -> Result<Self, Error> {
+        if vm.get_slot_type(slot) == Type::String {
+            let bytes: Box<dyn Any> = Box::new(slot_bytes(vm, slot)?.to_vec());
+            if let Ok(bytes) = bytes.downcast::<Vec<T>>() {
+                return Ok(*bytes);
+            }
+        }
+        vm.expect_type(slot, Type::List)?;
+        vm.restoring_slots(|vm| {
+            let element = vm.scratch_slots(1);
+            let count = vm.get_list_count(slot);
+            let mut list = Vec::with_capacity(count as usize);
+            for index in 0..count {
+                vm.list_get(slot, index, element);
+                list.push(T::from_wren(vm, element)?);
+            }
+            Ok(list)
+        })
+    }
+}
+
+impl<T: ToWren + 'static> ToWren for Vec<T> {
+    fn to_wren(&self, vm: &mut VM, slot: i32) {
+        if let Some(bytes) = (self as &dyn Any).downcast_ref::<Vec<u8>>() {
+            vm.set_slot_bytes(slot, bytes);
+            return;
+        }
+        vm.restoring_slots(|vm| {
+            let element = vm.scratch_slots(1);
+            vm.set_slot_new_list(slot);
+            for value in self {
+                value.to_wren(vm, element);
+                vm.list_push(slot, element);
+            }
+        })
+    }
+}
+
+impl VM {
+    /// Reads every entry of the map in `slot`, in no particular order.
+    pub fn map_entries<K, V>(&mut self, slot: i32) -> Result<Vec<(K, V)>, Error>
+    where
+        K: for<'b> FromWren<'b>,
+        V: for<'b> FromWren<'b>,
+    {
+        self.restoring_slots(|vm| {
+            let keys = vm.scratch_slots(4);
+            let values = keys + 1;
+            vm.map_keys(slot, keys)?;
+            vm.map_values(slot, values)?;
+            let count = vm.get_list_count(keys);
+            let mut entries = Vec::with_capacity(count as usize);
+            for index in 0..count {
+                vm.list_get(keys, index, keys + 2);
+                vm.list_get(values, index, keys + 3);
+                let key = K::from_wren(vm, keys + 2)?;
+                let value = V::from_wren(vm, keys + 3)?;
+                entries.push((key, value));
+            }
+            Ok(entries)
+        })
+    }
+    fn set_map_entries<'a, K, V, I>(&mut self, slot: i32, entries: I)
+    where
+        K: ToWren + 'a,
+        V: ToWren + 'a,
+        I: Iterator<Item = (&'a K, &'a V)>,
+    {
+        self.restoring_slots(|vm| {
+            let key = vm.scratch_slots(2);
+            vm.set_slot_new_map(slot);
+            for (k, v) in entries {
+                k.to_wren(vm, key);
+                v.to_wren(vm, key + 1);
+                vm.set_map_value(slot, key, key + 1);
+            }
+        })
+    }
+}
+
+impl<'a, K, V, S> FromWren<'a> for HashMap<K, V, S>
+where
+    K: for<'b> FromWren<'b> + Eq + Hash,
+    V: for<'b> FromWren<'b>,
+    S: BuildHasher + Default,
+{
+    fn from_wren(vm: &'a mut VM, slot: i32) -> Result<Self, Error> {
+        Ok(vm.map_entries(slot)?.into_iter().collect())
+    }
+}
+
+impl<K: ToWren, V: ToWren, S> ToWren for HashMap<K, V, S> {
+    fn to_wren(&self, vm: &mut VM, slot: i32) {
+        vm.set_map_entries(slot, self.iter())
+    }
+}
+
+impl<'a, K, V> FromWren<'a> for BTreeMap<K, V>
+where
+    K: for<'b> FromWren<'b> + Ord,
+    V: for<'b> FromWren<'b>,
+{
+    fn from_wren(vm: &'a mut VM, slot: i32) -> Result<Self, Error> {
+        Ok(vm.map_entries(slot)?.into_iter().collect())
+    }
+}
+
+impl<K: ToWren, V: ToWren> ToWren for BTreeMap<K, V> {
+    fn to_wren(&self, vm: &mut VM, slot: i32) {
+        vm.set_map_entries(slot, self.iter())
+    }
+}
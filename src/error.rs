@@ -0,0 +1,250 @@
+use std::ffi::NulError;
+use std::{error, fmt, io, str};
+
+use crate::ffi;
+use crate::{InterpretResult, Type};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub module: String,
+    pub line: i32,
+    pub function: String,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Compile {
+        module: String,
+        line: i32,
+        message: String,
+    },
+    Runtime {
+        message: String,
+        stack_trace: Vec<Frame>,
+    },
+    Io(io::Error),
+    Utf8(str::Utf8Error),
+    Nul(NulError),
+    Type {
+        expected: Type,
+        found: Type,
+    },
+    Num {
+        value: f64,
+        target: &'static str,
+    },
+    /// Calling into wren from inside a foreign method, which wren doesn't
+    /// allow. Everything that needs the map helpers counts, see helper.rs.
+    InForeignMethod,
+    Arity {
+        signature: String,
+        expected: usize,
+        found: usize,
+    },
+    Index {
+        index: i32,
+        count: i32,
+    },
+    Slot {
+        slot: i32,
+        count: i32,
+    },
+    /// A module that isn't loaded or a variable it doesn't have.
+    Variable {
+        module: String,
+        name: String,
+    },
+    /// A handle whose VM has been freed.
+    StaleHandle,
+    /// A handle used with a VM it doesn't belong to.
+    WrongVm,
+    /// All MAX_FOREIGN_METHODS dispatch slots of the VM are taken.
+    MethodLimit {
+        limit: usize,
+    },
+    /// A value that wren can't hash used as a map key.
+    MapKey {
+        found: Type,
+    },
+    /// Moving the clock of a VM that doesn't use a virtual one.
+    RealClock,
+    /// A slot expected to hold a fiber holds something else.
+    NotFiber {
+        found: Type,
+    },
+    /// A fiber iterated with `fiber_iter` suspended itself instead of yielding
+    /// an item.
+    Suspended,
+    /// Copying a list or map that contains itself.
+    Cycle,
+    /// From the serde (de)serializer, `path` leads to the offending value,
+    /// e.g. "servers[1].port".
+    Serde {
+        path: String,
+        message: String,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Compile {
+                module,
+                line,
+                message,
+            } => write!(f, "[{} line {}] {}", module, line, message),
+            Error::Runtime {
+                message,
+                stack_trace,
+            } => {
+                write!(f, "{}", message)?;
+                for frame in stack_trace {
+                    write!(
+                        f,
+                        "\n[{} line {}] in {}",
+                        frame.module, frame.line, frame.function
+                    )?;
+                }
+                Ok(())
+            }
+            Error::Io(err) => write!(f, "{}", err),
+            Error::Utf8(err) => write!(f, "string is not valid UTF-8: {}", err),
+            Error::Nul(err) => write!(f, "{}", err),
+            Error::Type { expected, found } => {
+                write!(f, "expected a {:?} value but found {:?}", expected, found)
+            }
+            Error::Num { value, target } => write!(f, "{} does not fit in {}", value, target),
+            Error::InForeignMethod => write!(f, "cannot call into wren from a foreign method"),
+            Error::Arity {
+                signature,
+                expected,
+                found,
+            } => write!(
+                f,
+                "`{}` takes {} arguments but {} were given",
+                signature, expected, found
+            ),
+            Error::Index { index, count } => {
+                write!(
+                    f,
+                    "index {} is out of bounds for a list of {}",
+                    index, count
+                )
+            }
+            Error::StaleHandle => write!(f, "handle used after its VM was freed"),
+            Error::WrongVm => write!(f, "handle belongs to a different VM"),
+            Error::MethodLimit { limit } => write!(
+                f,
+                "no more than {} foreign closures can be bound to a VM",
+                limit
+            ),
+            Error::MapKey { found } => write!(f, "a {:?} can't be used as a map key", found),
+            Error::Slot { slot, count } => {
+                write!(f, "slot {} is out of bounds, there are {}", slot, count)
+            }
+            Error::Variable { module, name } => {
+                write!(f, "no variable `{}` in module \"{}\"", name, module)
+            }
+            Error::RealClock => write!(f, "only a virtual clock can be advanced"),
+            Error::NotFiber { found } => write!(f, "expected a fiber but found {:?}", found),
+            Error::Suspended => write!(f, "the fiber was suspended before returning"),
+            Error::Cycle => write!(f, "value contains itself"),
+            Error::Serde { path, message } if path.is_empty() => write!(f, "{}", message),
+            Error::Serde { path, message } => write!(f, "{}: {}", path, message),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::Utf8(err) => Some(err),
+            Error::Nul(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+impl From<str::Utf8Error> for Error {
+    fn from(err: str::Utf8Error) -> Error {
+        Error::Utf8(err)
+    }
+}
+
+impl From<NulError> for Error {
+    fn from(err: NulError) -> Error {
+        Error::Nul(err)
+    }
+}
+
+// Collects what wren reports through the error callback while an interpret or
+// call is running.
+#[derive(Default)]
+pub(crate) struct ErrorBuffer {
+    compile: Vec<(String, i32, String)>,
+    runtime: Option<String>,
+    stack_trace: Vec<Frame>,
+    notes: Vec<String>,
+}
+
+impl ErrorBuffer {
+    pub(crate) fn push(
+        &mut self,
+        _type: ffi::WrenErrorType,
+        module: &str,
+        line: i32,
+        message: &str,
+    ) {
+        match _type {
+            ffi::WrenErrorType::Compile => {
+                self.compile
+                    .push((module.to_string(), line, message.to_string()))
+            }
+            ffi::WrenErrorType::Runtime => self.runtime = Some(message.to_string()),
+            ffi::WrenErrorType::StackTrace => self.stack_trace.push(Frame {
+                module: module.to_string(),
+                line,
+                function: message.to_string(),
+            }),
+        }
+    }
+
+    // Extra context from the crate for the next runtime error.
+    pub(crate) fn note(&mut self, note: String) {
+        self.notes.push(note);
+    }
+
+    pub(crate) fn into_error(self, result: InterpretResult) -> Error {
+        let mut compile = self.compile.into_iter();
+        if result == InterpretResult::CompileError {
+            if let Some((module, line, mut message)) = compile.next() {
+                // Later errors are usually knock-on effects of the first, but
+                // keep them around.
+                for (module, line, other) in compile {
+                    message.push_str(&format!("\n[{} line {}] {}", module, line, other));
+                }
+                return Error::Compile {
+                    module,
+                    line,
+                    message,
+                };
+            }
+        }
+        let mut message = self.runtime.unwrap_or_else(|| format!("{:?}", result));
+        for note in self.notes {
+            message.push('\n');
+            message.push_str(&note);
+        }
+        Error::Runtime {
+            message,
+            stack_trace: self.stack_trace,
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use std::marker::PhantomData;
+
+use crate::call::Args;
+use crate::convert::{FromWren, ToWren};
+use crate::ffi;
+use crate::vm::Handle;
+use crate::{Error, Type, Value, VM};
+
+/// A wren fiber held by handle. Everything goes through the fiber's own
+/// methods, so the usual wren rules apply: a fiber that is done can't be
+/// resumed, and only `try_call` keeps a runtime error inside the fiber.
+pub struct Fiber {
+    handle: Handle,
+}
+
+impl VM {
+    /// Creates a fiber that runs `function`, a wren Fn taking at most one
+    /// argument.
+    pub fn new_fiber(&mut self, function: &Handle) -> Result<Fiber, Error> {
+        if self.in_foreign_method() {
+            return Err(Error::InForeignMethod);
+        }
+        let helper = self.data().helper.take().ok_or(Error::InForeignMethod)?;
+        let fiber = self.call_method::<Handle, _>(&helper.fiber_class, "new", (function,));
+        self.data().helper = Some(helper);
+        Ok(Fiber { handle: fiber? })
+    }
+    pub(crate) fn is_fiber(&mut self, slot: i32) -> bool {
+        self.get_slot_type(slot) == Type::Unknown
+            && unsafe { ffi::wrenGetSlotIsFiber(self.raw, slot) }
+    }
+}
+
+impl Fiber {
+    /// For fibers that were created by a script.
+    pub fn from_handle(handle: Handle) -> Fiber {
+        Fiber { handle }
+    }
+    pub fn handle(&self) -> &Handle {
+        &self.handle
+    }
+    /// Runs the fiber until it yields or returns and gives back the value it
+    /// yielded or returned. `args` is `()` or a single value that the fiber
+    /// receives as the result of `Fiber.yield()`, or as the argument of its
+    /// function when it starts.
+    pub fn call<'a, R: FromWren<'a>, A: Args>(&self, vm: &'a mut VM, args: A) -> Result<R, Error> {
+        vm.call_method(&self.handle, "call", args)
+    }
+    /// Switches to the fiber without making it return here. The call comes
+    /// back with what the fiber returns when it finishes, or with null when it
+    /// yields or suspends instead, since there is no caller to yield to.
+    pub fn transfer<'a, R: FromWren<'a>, A: Args>(
+        &self,
+        vm: &'a mut VM,
+        args: A,
+    ) -> Result<R, Error> {
+        vm.call_method(&self.handle, "transfer", args)
+    }
+    /// Like `call`, but a runtime error in the fiber ends up in `Err` with the
+    /// value the fiber was aborted with, instead of failing the call.
+    pub fn try_call<A: Args>(&self, vm: &mut VM, args: A) -> Result<Result<Value, Value>, Error> {
+        let value: Value = vm.call_method(&self.handle, "try", args)?;
+        match self.error(vm)? {
+            Some(error) => Ok(Err(error)),
+            None => Ok(Ok(value)),
+        }
+    }
+    pub fn is_done(&self, vm: &mut VM) -> Result<bool, Error> {
+        vm.call_method(&self.handle, "isDone", ())
+    }
+    /// What the fiber was aborted with, if it was.
+    pub fn error(&self, vm: &mut VM) -> Result<Option<Value>, Error> {
+        match vm.call_method(&self.handle, "error", ())? {
+            Value::Null => Ok(None),
+            error => Ok(Some(error)),
+        }
+    }
+}
+
+impl<'a> FromWren<'a> for Fiber {
+    fn from_wren(vm: &'a mut VM, slot: i32) -> Result<Self, Error> {
+        if !vm.is_fiber(slot) {
+            let found = vm.get_slot_type(slot);
+            return Err(Error::NotFiber { found });
+        }
+        Ok(Fiber::from_handle(vm.get_slot_handle(slot)))
+    }
+}
+
+impl ToWren for Fiber {
+    fn to_wren(&self, vm: &mut VM, slot: i32) {
+        vm.set_slot_handle(slot, &self.handle)
+    }
+}
+
+impl VM {
+    /// Resumes `fiber` for every item it yields. Whatever the fiber returns
+    /// when it finishes is not an item.
+    pub fn fiber_iter<'a, 'f, T>(&'a mut self, fiber: &'f Handle) -> FiberIter<'a, 'f, T>
+    where
+        T: for<'b> FromWren<'b>,
+    {
+        FiberIter {
+            vm: self,
+            fiber,
+            started: false,
+            done: false,
+            item: PhantomData,
+        }
+    }
+}
+
+impl Fiber {
+    pub fn iter<'a, 'f, T>(&'f self, vm: &'a mut VM) -> FiberIter<'a, 'f, T>
+    where
+        T: for<'b> FromWren<'b>,
+    {
+        vm.fiber_iter(&self.handle)
+    }
+}
+
+pub struct FiberIter<'a, 'f, T> {
+    vm: &'a mut VM,
+    fiber: &'f Handle,
+    started: bool,
+    done: bool,
+    item: PhantomData<fn() -> T>,
+}
+
+impl<T> FiberIter<'_, '_, T>
+where
+    T: for<'b> FromWren<'b>,
+{
+    fn is_done(&mut self) -> Result<bool, Error> {
+        self.vm.call_method(self.fiber, "isDone", ())
+    }
+
+    fn resume(&mut self) -> Result<Option<T>, Error> {
+        if !self.started {
+            self.started = true;
+            if self.is_done()? {
+                return Ok(None);
+            }
+        }
+        if !self.vm.send(self.fiber, "call", ())? {
+            return Err(Error::Suspended);
+        }
+        // The value is kept by handle while asking the fiber whether it is
+        // done, which needs the slots.
+        let value = self.vm.get_slot_handle(0);
+        if self.is_done()? {
+            return Ok(None);
+        }
+        self.vm.ensure_slots(1);
+        self.vm.set_slot_handle(0, &value);
+        T::from_wren(self.vm, 0).map(Some)
+    }
+}
+
+impl<T> Iterator for FiberIter<'_, '_, T>
+where
+    T: for<'b> FromWren<'b>,
+{
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Result<T, Error>> {
+        if self.done {
+            return None;
+        }
+        match self.resume() {
+            Ok(Some(item)) => Some(Ok(item)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            // A bad item doesn't hurt the fiber, anything else ends it.
+            Err(error @ (Error::Type { .. } | Error::Num { .. } | Error::Utf8(_))) => {
+                Some(Err(error))
+            }
+            Err(error) => {
+                self.done = true;
+                Some(Err(error))
+            }
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use libc::c_void;
+use std::any::TypeId;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+use crate::ffi;
+use crate::macros;
+use crate::vm::{ForeignData, ForeignMethod};
+use crate::{Type, VM};
+
+pub trait ForeignClass: Sized + 'static {
+    fn construct(vm: &mut VM) -> Result<Self, String>;
+    fn methods(_methods: &mut ClassMethods<Self>) {}
+}
+
+pub struct ClassMethods<T> {
+    methods: Vec<(bool, String, ForeignMethod)>,
+    marker: PhantomData<T>,
+}
+
+impl<T: ForeignClass> ClassMethods<T> {
+    pub(crate) fn new() -> ClassMethods<T> {
+        ClassMethods {
+            methods: Vec::new(),
+            marker: PhantomData,
+        }
+    }
+    pub(crate) fn into_methods(self) -> Vec<(bool, String, ForeignMethod)> {
+        self.methods
+    }
+    pub fn method<F: FnMut(&mut T, &mut VM) + 'static>(&mut self, signature: &str, mut f: F) {
+        let method = move |vm: &mut VM| match vm.get_slot_foreign_class::<T>(0) {
+            Some(mut this) => f(this.inner(), vm),
+            None => {
+                vm.set_slot_string(0, "Receiver is not an instance of the foreign class");
+                vm.abort_fiber(0);
+            }
+        };
+        self.methods
+            .push((false, signature.to_string(), Box::new(method)));
+    }
+    pub fn static_method<F: FnMut(&mut VM) + 'static>(&mut self, signature: &str, f: F) {
+        self.methods
+            .push((true, signature.to_string(), Box::new(f)));
+    }
+}
+
+thread_local! {
+    // The foreign data of every live object made by `allocate`, with the type
+    // of the value it points to. Keyed by wren's data pointer so other foreign objects are
+    // never read. Finalizers only receive the foreign data, so this can't be
+    // kept on the VM.
+    static LIVE: RefCell<HashMap<usize, TypeId>> = RefCell::new(HashMap::new());
+}
+
+// The object lives in a Rust allocation and wren's foreign data only holds a
+// pointer to it, so any alignment works.
+pub(crate) unsafe extern "C" fn allocate<T: ForeignClass>(vm: *mut ffi::WrenVM) {
+    let mut vm = VM::from_ptr(vm);
+    macros::call_foreign(&mut vm, |vm| match T::construct(vm) {
+        Ok(value) => {
+            let data = vm.set_slot_new_foreign::<*mut T>(0, 0);
+            ptr::write(data, Box::into_raw(Box::new(value)));
+            LIVE.with(|live| live.borrow_mut().insert(data as usize, TypeId::of::<T>()));
+        }
+        Err(message) => {
+            vm.set_slot_string(0, &message);
+            vm.abort_fiber(0);
+        }
+    });
+}
+
+pub(crate) unsafe extern "C" fn finalize<T: ForeignClass>(data: *mut c_void) {
+    LIVE.with(|live| live.borrow_mut().remove(&(data as usize)));
+    let value = *(data as *mut *mut T);
+    // A panicking Drop can't be reported anywhere, the object is gone either
+    // way.
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(Box::from_raw(value))));
+}
+
+impl VM {
+    pub fn get_slot_foreign_class<T: ForeignClass>(&mut self, slot: i32) -> Option<ForeignData<T>> {
+        if self.get_slot_type(slot) != Type::Foreign {
+            return None;
+        }
+        let data = self.get_slot_foreign::<*mut T>(slot);
+        // Only objects allocated by `allocate` are known to hold a pointer to
+        // a `T`.
+        let type_id = LIVE.with(|live| live.borrow().get(&(data as usize)).copied());
+        if type_id != Some(TypeId::of::<T>()) {
+            return None;
+        }
+        unsafe {
+            let value = ptr::read_unaligned(data);
+            Some(ForeignData::new(&mut *value))
+        }
+    }
+}
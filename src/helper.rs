@@ -0,0 +1,120 @@
+use std::ffi::CString;
+
+use crate::ffi;
+use crate::vm::Handle;
+use crate::{Error, InterpretResult, Type, VM};
+
+// The C API has no way to enumerate a map, so every VM gets a tiny module that
+// does it from wren and is reached through call handles. wren can't be entered
+// again from inside a foreign method, so there everything built on it fails
+// with `Error::InForeignMethod`: reading maps into a HashMap, BTreeMap or
+// Value, `map_entries`, deserializing maps, `remove_from_list` and
+// `clear_list`. Pass `map.keys.toList` and `map.values.toList` from the script
+// instead.
+const MODULE: &str = "wren-rs";
+const SOURCE: &str = "class Helper {
+  static keys(map) { map.keys.toList }
+  static values(map) { map.values.toList }
+  static removeAt(list, index) { list.removeAt(index) }
+  static clear(list) { list.clear() }
+}
+";
+
+pub(crate) struct Helper {
+    class: Handle,
+    pub(crate) fiber_class: Handle,
+    keys: Handle,
+    values: Handle,
+    remove_at: Handle,
+    clear: Handle,
+}
+
+impl Helper {
+    pub(crate) fn new(vm: &mut VM) -> Helper {
+        let module = CString::new(MODULE).unwrap();
+        let source = CString::new(SOURCE).unwrap();
+        let result = unsafe { ffi::wrenInterpret(vm.raw, module.as_ptr(), source.as_ptr()) };
+        assert!(
+            result == InterpretResult::Success,
+            "Failed to load the helper module"
+        );
+        vm.ensure_slots(1);
+        vm.get_variable(MODULE, "Helper", 0).unwrap();
+        let class = vm.get_slot_handle(0);
+        vm.get_variable(MODULE, "Fiber", 0).unwrap();
+        Helper {
+            class,
+            fiber_class: vm.get_slot_handle(0),
+            keys: vm.make_call_handle("keys(_)").unwrap(),
+            values: vm.make_call_handle("values(_)").unwrap(),
+            remove_at: vm.make_call_handle("removeAt(_,_)").unwrap(),
+            clear: vm.make_call_handle("clear(_)").unwrap(),
+        }
+    }
+}
+
+impl VM {
+    // Stores a list of the keys of the map in `map_slot` into `keys_slot`.
+    pub(crate) fn map_keys(&mut self, map_slot: i32, keys_slot: i32) -> Result<(), Error> {
+        self.expect_type(map_slot, Type::Map)?;
+        self.call_helper(|helper| &helper.keys, &[map_slot], Some(keys_slot))
+    }
+    // The values come in the same order as the keys from `map_keys`.
+    pub(crate) fn map_values(&mut self, map_slot: i32, values_slot: i32) -> Result<(), Error> {
+        self.expect_type(map_slot, Type::Map)?;
+        self.call_helper(|helper| &helper.values, &[map_slot], Some(values_slot))
+    }
+    pub(crate) fn list_remove(
+        &mut self,
+        list_slot: i32,
+        index: i32,
+        removed_slot: i32,
+    ) -> Result<(), Error> {
+        self.restoring_slots(|vm| {
+            let index_slot = vm.scratch_slots(1);
+            vm.set_slot_double(index_slot, index as f64);
+            let args = [list_slot, index_slot];
+            vm.call_helper(|helper| &helper.remove_at, &args, Some(removed_slot))
+        })
+    }
+    pub(crate) fn list_clear(&mut self, list_slot: i32) -> Result<(), Error> {
+        self.call_helper(|helper| &helper.clear, &[list_slot], None)
+    }
+    // Calls a helper method with the values in `args` and stores what it
+    // returns into `result_slot`. wrenCall() reuses the slot array, so every
+    // slot is parked in a handle for the duration of the call and put back
+    // afterwards.
+    fn call_helper<F: Fn(&Helper) -> &Handle>(
+        &mut self,
+        method: F,
+        args: &[i32],
+        result_slot: Option<i32>,
+    ) -> Result<(), Error> {
+        if self.in_foreign_method() {
+            return Err(Error::InForeignMethod);
+        }
+
+        let count = self.get_slot_count();
+        let saved: Vec<Handle> = (0..count).map(|slot| self.get_slot_handle(slot)).collect();
+
+        let helper = self.data().helper.take().ok_or(Error::InForeignMethod)?;
+        self.ensure_slots(args.len() as i32 + 1);
+        self.set_slot_handle(0, &helper.class);
+        for (i, arg) in args.iter().enumerate() {
+            self.set_slot_handle(i as i32 + 1, &saved[*arg as usize]);
+        }
+        let result = self.call(method(&helper));
+        let returned = self.get_slot_handle(0);
+        self.data().helper = Some(helper);
+
+        let needed = result_slot.map_or(count, |slot| count.max(slot + 1));
+        self.ensure_slots(needed);
+        for (slot, handle) in saved.iter().enumerate() {
+            self.set_slot_handle(slot as i32, handle);
+        }
+        if let Some(slot) = result_slot {
+            self.set_slot_handle(slot, &returned);
+        }
+        result
+    }
+}
@@ -0,0 +1,99 @@
+use libc::c_char;
+use std::ffi::CString;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::{panic, ptr};
+
+use crate::ffi;
+use crate::VM;
+
+// Reading the clock on every check would dominate tight loops.
+const CLOCK_INTERVAL: u64 = 1024;
+
+/// Stops whatever the VM is running from another thread. A request made while
+/// the VM is idle aborts the next script as soon as it starts.
+#[derive(Clone)]
+pub struct InterruptHandle {
+    flag: Arc<AtomicBool>,
+}
+
+impl InterruptHandle {
+    pub fn interrupt(&self) {
+        self.flag.store(true, Ordering::Relaxed);
+    }
+}
+
+// Limits for a single interpret or call. Steps are counted at the points
+// where the interpreter checks for interrupts, loop iterations and method
+// calls, so they bound the work a script can do without being exact
+// instruction counts.
+#[derive(Default)]
+pub(crate) struct Budget {
+    flag: Arc<AtomicBool>,
+    pub(crate) time_limit: Option<Duration>,
+    pub(crate) step_limit: Option<u64>,
+    deadline: Option<Instant>,
+    steps: u64,
+    interrupted: bool,
+}
+
+impl Budget {
+    pub(crate) fn start(&mut self) {
+        self.steps = 0;
+        self.interrupted = false;
+        self.deadline = self.time_limit.map(|limit| Instant::now() + limit);
+    }
+
+    fn check(&mut self) -> Option<&'static str> {
+        // Once tripped, every check fails until the run is over, so a script
+        // can't carry on by catching the error with Fiber.try().
+        if self.interrupted || self.flag.swap(false, Ordering::Relaxed) {
+            self.interrupted = true;
+            return Some("Execution interrupted.");
+        }
+        self.steps += 1;
+        if self.step_limit.is_some_and(|limit| self.steps > limit) {
+            return Some("Execution interrupted: step limit exceeded.");
+        }
+        if let Some(deadline) = self.deadline {
+            if self.steps.is_multiple_of(CLOCK_INTERVAL) && Instant::now() >= deadline {
+                return Some("Execution interrupted: time limit exceeded.");
+            }
+        }
+        None
+    }
+}
+
+// Polled by the interpreter on loop back-edges and calls. Returning a message
+// aborts the running fiber with it.
+pub(crate) unsafe extern "C" fn interrupt(vm: *mut ffi::WrenVM) -> *const c_char {
+    panic::catch_unwind(|| {
+        let mut vm = VM::from_ptr(vm);
+        let message = match vm.data().budget.check() {
+            Some(message) => message,
+            None => return ptr::null(),
+        };
+        let message = vm
+            .data()
+            .interrupt_message
+            .insert(CString::new(message).unwrap());
+        message.as_ptr()
+    })
+    .unwrap_or(ptr::null())
+}
+
+impl VM {
+    pub fn interrupt_handle(&mut self) -> InterruptHandle {
+        InterruptHandle {
+            flag: self.data().budget.flag.clone(),
+        }
+    }
+    /// Applies to every interpret and call from the next one on.
+    pub fn set_time_limit(&mut self, limit: Option<Duration>) {
+        self.data().budget.time_limit = limit;
+    }
+    pub fn set_step_limit(&mut self, limit: Option<u64>) {
+        self.data().budget.step_limit = limit;
+    }
+}
@@ -2,13 +2,41 @@ use wren_sys as ffi;
 
 #[macro_use]
 pub mod macros;
+mod call;
+mod convert;
+mod error;
+mod fiber;
+mod foreign;
+mod helper;
+mod interrupt;
+mod loader;
+mod memory;
+mod output;
+pub mod path;
+mod scheduler;
+#[cfg(feature = "serde")]
+pub mod serialize;
+#[cfg(feature = "async")]
+mod task;
+mod value;
 mod vm;
 pub use ffi::WrenForeignClassMethods as ForeignClassMethods; // 待优化
 pub use ffi::WrenForeignMethodFn as ForeignMethodFn; // 待优化
 pub use ffi::WrenInterpretResult as InterpretResult;
+pub use fiber::{Fiber, FiberIter};
+pub use foreign::{ClassMethods, ForeignClass};
 // pub use ffi::WrenLoadModuleResult as LoadModuleResult; // 待优化
+pub use call::Args;
+pub use convert::{FromWren, ToWren};
+pub use error::{Error, Frame};
 pub use ffi::WrenType as Type;
+pub use interrupt::InterruptHandle;
+pub use loader::{ChainLoader, EmbeddedLoader, FileLoader, MemoryLoader, ModuleLoader, Source};
+pub use macros::MAX_FOREIGN_METHODS;
+pub use memory::{GcStats, MemoryStats};
+pub use value::Value;
 pub use vm::Configuration;
+pub use vm::ForeignMethod;
 pub use vm::Handle;
 pub use vm::VM;
 pub type Point = *mut libc::c_void;
@@ -0,0 +1,233 @@
+use libc::c_char;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::{fs, ptr};
+
+use crate::ffi;
+use crate::macros;
+use crate::path::{self, PathType};
+use crate::scheduler;
+use crate::VM;
+
+pub enum Source {
+    Static(&'static str),
+    Owned(String),
+}
+
+impl Source {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Source::Static(source) => source,
+            Source::Owned(source) => source,
+        }
+    }
+}
+
+impl From<&'static str> for Source {
+    fn from(source: &'static str) -> Source {
+        Source::Static(source)
+    }
+}
+
+impl From<String> for Source {
+    fn from(source: String) -> Source {
+        Source::Owned(source)
+    }
+}
+
+pub trait ModuleLoader {
+    fn resolve(&self, importer: &str, name: &str) -> Option<String> {
+        Some(path::resolve(importer, name))
+    }
+    fn load(&self, name: &str) -> Option<Source>;
+}
+
+/// Relative and absolute module names are file paths, logical ones like
+/// "json" are looked up in the module directories, either as "json.wren" or as
+/// the package "json/json.wren". Relative imports from a logical module are
+/// taken from the directory of the file it was found in.
+pub struct FileLoader {
+    module_dirs: Vec<PathBuf>,
+    // The file each logical module came from, as a path without the
+    // extension.
+    files: RefCell<HashMap<String, String>>,
+}
+
+impl FileLoader {
+    pub fn new() -> FileLoader {
+        FileLoader {
+            module_dirs: vec![PathBuf::from("wren_modules")],
+            files: RefCell::new(HashMap::new()),
+        }
+    }
+    pub fn with_modules_dir<P: AsRef<Path>>(mut self, dir: P) -> FileLoader {
+        self.module_dirs.push(dir.as_ref().to_path_buf());
+        self
+    }
+    fn read(path: PathBuf) -> Option<Source> {
+        fs::read_to_string(path).ok().map(Source::Owned)
+    }
+    fn read_module(&self, name: &str, file: PathBuf) -> Option<Source> {
+        let source = FileLoader::read(file.clone())?;
+        let file = file.to_string_lossy();
+        let file = path::remove_extension(&file);
+        // "wren_modules/json" would be taken for a logical name.
+        let file = match path::path_type(file) {
+            PathType::Simple => format!("./{}", file),
+            _ => file.to_string(),
+        };
+        self.files.borrow_mut().insert(name.to_string(), file);
+        Some(source)
+    }
+}
+
+impl Default for FileLoader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ModuleLoader for FileLoader {
+    fn resolve(&self, importer: &str, name: &str) -> Option<String> {
+        let files = self.files.borrow();
+        let importer = files.get(importer).map_or(importer, String::as_str);
+        Some(path::resolve(importer, name))
+    }
+    fn load(&self, name: &str) -> Option<Source> {
+        let file = format!("{}.wren", name);
+        if path::path_type(name) != PathType::Simple {
+            return FileLoader::read(PathBuf::from(file));
+        }
+        self.module_dirs.iter().find_map(|dir| {
+            self.read_module(name, dir.join(&file)).or_else(|| {
+                let base = name.rsplit('/').next().unwrap_or(name);
+                self.read_module(name, dir.join(name).join(format!("{}.wren", base)))
+            })
+        })
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryLoader {
+    modules: HashMap<String, String>,
+}
+
+impl MemoryLoader {
+    pub fn new() -> MemoryLoader {
+        MemoryLoader::default()
+    }
+    pub fn insert<S: Into<String>>(&mut self, name: &str, source: S) -> &mut MemoryLoader {
+        self.modules.insert(name.to_string(), source.into());
+        self
+    }
+}
+
+impl ModuleLoader for MemoryLoader {
+    fn load(&self, name: &str) -> Option<Source> {
+        self.modules
+            .get(name)
+            .map(|source| Source::Owned(source.clone()))
+    }
+}
+
+/// For modules compiled into the binary with include_str!.
+pub struct EmbeddedLoader {
+    modules: &'static [(&'static str, &'static str)],
+}
+
+impl EmbeddedLoader {
+    pub const fn new(modules: &'static [(&'static str, &'static str)]) -> EmbeddedLoader {
+        EmbeddedLoader { modules }
+    }
+}
+
+impl ModuleLoader for EmbeddedLoader {
+    fn load(&self, name: &str) -> Option<Source> {
+        self.modules
+            .iter()
+            .find(|(module, _)| *module == name)
+            .map(|(_, source)| Source::Static(source))
+    }
+}
+
+/// Asks each loader in turn and goes with the first one that answers. Imports
+/// from a module are resolved by the loader that loaded it.
+#[derive(Default)]
+pub struct ChainLoader {
+    loaders: Vec<Box<dyn ModuleLoader>>,
+    owners: RefCell<HashMap<String, usize>>,
+}
+
+impl ChainLoader {
+    pub fn new() -> ChainLoader {
+        ChainLoader::default()
+    }
+    pub fn with<L: ModuleLoader + 'static>(mut self, loader: L) -> ChainLoader {
+        self.loaders.push(Box::new(loader));
+        self
+    }
+}
+
+impl ModuleLoader for ChainLoader {
+    fn resolve(&self, importer: &str, name: &str) -> Option<String> {
+        if let Some(&owner) = self.owners.borrow().get(importer) {
+            return self.loaders[owner].resolve(importer, name);
+        }
+        self.loaders
+            .iter()
+            .find_map(|loader| loader.resolve(importer, name))
+    }
+    fn load(&self, name: &str) -> Option<Source> {
+        let (owner, source) = self
+            .loaders
+            .iter()
+            .enumerate()
+            .find_map(|(i, loader)| Some((i, loader.load(name)?)))?;
+        self.owners.borrow_mut().insert(name.to_string(), owner);
+        Some(source)
+    }
+}
+
+pub(crate) unsafe extern "C" fn resolve_module(
+    vm: *mut ffi::WrenVM,
+    importer: *const c_char,
+    name: *const c_char,
+) -> *const c_char {
+    panic::catch_unwind(|| {
+        let mut vm = VM::from_ptr(vm);
+        let importer = CStr::from_ptr(importer).to_string_lossy();
+        let name = CStr::from_ptr(name).to_string_lossy();
+        let resolved = match &vm.data().loader {
+            Some(loader) => loader.resolve(&importer, &name),
+            None => Some(name.to_string()),
+        };
+        match resolved {
+            // wren frees the resolved name with its own reallocator.
+            Some(resolved) => vm.alloc_c_string(resolved.as_bytes()),
+            None => ptr::null(),
+        }
+    })
+    .unwrap_or(ptr::null())
+}
+
+pub(crate) unsafe extern "C" fn load_module(
+    vm: *mut ffi::WrenVM,
+    name: *const c_char,
+) -> ffi::WrenLoadModuleResult {
+    let source = panic::catch_unwind(|| {
+        let mut vm = VM::from_ptr(vm);
+        let name = CStr::from_ptr(name).to_string_lossy();
+        // Built-in modules come after the loader, so scripts can replace them.
+        let data = vm.data();
+        let source = match data.loader.as_ref().and_then(|loader| loader.load(&name)) {
+            Some(source) => source,
+            None if data.scheduler => Source::Static(scheduler::module(&name)?),
+            None => return None,
+        };
+        CString::new(source.as_str()).ok()
+    });
+    macros::load_module_result(source.ok().flatten())
+}
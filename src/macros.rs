@@ -1,8 +1,11 @@
 use crate::ffi;
 use crate::VM;
 use libc::{c_char, c_int, c_void};
-use std::ffi::CStr;
+use std::any::Any;
+use std::borrow::Cow;
+use std::ffi::{CStr, CString};
 use std::mem;
+use std::panic::{self, AssertUnwindSafe};
 use std::ptr;
 
 #[macro_export]
@@ -75,14 +78,48 @@ fn _asset_size<F>() {
     assert!(size == 0, "Wrapped closures must be zero-sized");
 }
 
+// Nothing may unwind into the C interpreter, so every trampoline runs the
+// wrapped function under catch_unwind and turns a panic into something wren
+// can deal with.
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Foreign method panicked".to_string()
+    }
+}
+
+// Runs a foreign method, reporting a panic to the calling fiber as a runtime
+// error.
+pub(crate) fn call_foreign<F: FnOnce(&mut VM)>(vm: &mut VM, f: F) {
+    vm.enter_foreign_method();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| f(vm)));
+    vm.leave_foreign_method();
+    if let Err(payload) = result {
+        let message = panic_message(&*payload);
+        vm.ensure_slots(1);
+        vm.set_slot_string(0, &message);
+        vm.abort_fiber(0);
+    }
+}
+
+unsafe fn to_str<'a>(s: *const c_char) -> Cow<'a, str> {
+    if s.is_null() {
+        Cow::Borrowed("")
+    } else {
+        CStr::from_ptr(s).to_string_lossy()
+    }
+}
+
 #[doc(hidden)]
 #[inline]
 pub fn _write_fn<F: Fn(&mut VM, &str)>(_: F) -> ffi::WrenWriteFn {
     unsafe extern "C" fn f<F: Fn(&mut VM, &str)>(vm: *mut ffi::WrenVM, text: *const c_char) {
-        mem::transmute::<&(), &F>(&())(
-            &mut VM::from_ptr(vm),
-            CStr::from_ptr(text).to_str().unwrap(),
-        );
+        let _ = panic::catch_unwind(|| {
+            mem::transmute::<&(), &F>(&())(&mut VM::from_ptr(vm), &to_str(text));
+        });
     }
     _asset_size::<F>();
     Some(f::<F>)
@@ -98,26 +135,40 @@ pub fn _error_fn<F: Fn(&mut VM, ffi::WrenErrorType, &str, i32, &str)>(_: F) -> f
         line: c_int,
         message: *const c_char,
     ) {
-        let mut vm = VM::from_ptr(vm);
-        let module = if module.is_null() {
-            ""
-        } else {
-            CStr::from_ptr(module).to_str().unwrap()
-        };
-        let message = CStr::from_ptr(message).to_str().unwrap();
-        mem::transmute::<&(), &F>(&())(&mut vm, _type, module, line, message);
+        let _ = panic::catch_unwind(|| {
+            let mut vm = VM::from_ptr(vm);
+            mem::transmute::<&(), &F>(&())(&mut vm, _type, &to_str(module), line, &to_str(message));
+        });
     }
     _asset_size::<F>();
     Some(f::<F>)
 }
 
-unsafe extern "C" fn _load_module_complete(
+// The source handed to wren is a leaked CString that comes back here once the
+// module has been compiled.
+pub(crate) unsafe extern "C" fn _load_module_complete(
     _: *mut ffi::WrenVM,
     _: *const c_char,
     result: ffi::WrenLoadModuleResult,
 ) {
-    if result.source.is_null() {
-        libc::free(result.source as *mut c_void);
+    if !result.source.is_null() {
+        drop(CString::from_raw(result.source as *mut c_char));
+    }
+}
+// What every load_module_fn hands back to wren. A null source makes wren
+// report that the module could not be loaded.
+pub(crate) fn load_module_result(source: Option<CString>) -> ffi::WrenLoadModuleResult {
+    match source {
+        Some(source) => ffi::WrenLoadModuleResult {
+            source: source.into_raw(),
+            on_complete: Some(_load_module_complete),
+            user_data: ptr::null_mut(),
+        },
+        None => ffi::WrenLoadModuleResult {
+            source: ptr::null(),
+            on_complete: None,
+            user_data: ptr::null_mut(),
+        },
     }
 }
 #[doc(hidden)]
@@ -127,18 +178,11 @@ pub fn _load_module_fn<F: Fn(&mut VM, &str) -> Vec<u8>>(_: F) -> ffi::WrenLoadMo
         vm: *mut ffi::WrenVM,
         module: *const c_char,
     ) -> ffi::WrenLoadModuleResult {
-        let mut source = mem::transmute::<&(), &F>(&())(
-            &mut VM::from_ptr(vm),
-            CStr::from_ptr(module).to_str().unwrap(),
-        );
-        source.push(b'\0');
-        let source = CStr::from_bytes_with_nul(source.as_slice()).unwrap();
-
-        ffi::WrenLoadModuleResult {
-            source: source.as_ptr(),
-            on_complete: Some(_load_module_complete),
-            user_data: ptr::null_mut(),
-        }
+        let source = panic::catch_unwind(|| {
+            let source = mem::transmute::<&(), &F>(&())(&mut VM::from_ptr(vm), &to_str(module));
+            CString::new(source).ok()
+        });
+        load_module_result(source.ok().flatten())
     }
 
     _asset_size::<F>();
@@ -153,14 +197,14 @@ pub fn _resolve_module_fn<F: Fn(&mut VM, &str, &str) -> Vec<u8>>(_: F) -> ffi::W
         module: *const c_char,
         importer: *const c_char,
     ) -> *const c_char {
-        let mut path = mem::transmute::<&(), &F>(&())(
-            &mut VM::from_ptr(vm),
-            CStr::from_ptr(module).to_str().unwrap(),
-            CStr::from_ptr(importer).to_str().unwrap(),
-        );
-        path.push(b'\0');
-        let path = CStr::from_bytes_with_nul(path.as_slice()).unwrap();
-        path.as_ptr()
+        let path = panic::catch_unwind(|| {
+            let mut vm = VM::from_ptr(vm);
+            let path = mem::transmute::<&(), &F>(&())(&mut vm, &to_str(module), &to_str(importer));
+            // wren frees the resolved name with its own reallocator.
+            vm.alloc_c_string(&path)
+        });
+        // A null name makes wren report that the module could not be resolved.
+        path.unwrap_or(ptr::null())
     }
 
     _asset_size::<F>();
@@ -171,7 +215,8 @@ pub fn _resolve_module_fn<F: Fn(&mut VM, &str, &str) -> Vec<u8>>(_: F) -> ffi::W
 #[inline]
 pub fn _foreign_method_fn<F: Fn(&mut VM)>(_: F) -> ffi::WrenForeignMethodFn {
     unsafe extern "C" fn f<F: Fn(&mut VM)>(vm: *mut ffi::WrenVM) {
-        mem::transmute::<&(), &F>(&())(&mut VM::from_ptr(vm));
+        let mut vm = VM::from_ptr(vm);
+        call_foreign(&mut vm, |vm| mem::transmute::<&(), &F>(&())(vm));
     }
     _asset_size::<F>();
     Some(f::<F>)
@@ -181,7 +226,9 @@ pub fn _foreign_method_fn<F: Fn(&mut VM)>(_: F) -> ffi::WrenForeignMethodFn {
 #[inline]
 pub fn _finalizer_fn<F: Fn(*mut c_void)>(_: F) -> ffi::WrenFinalizerFn {
     unsafe extern "C" fn f<F: Fn(*mut c_void)>(data: *mut c_void) {
-        mem::transmute::<&(), &F>(&())(data)
+        // Finalizers run in the middle of a collection, there is nobody to
+        // report a panic to.
+        let _ = panic::catch_unwind(|| mem::transmute::<&(), &F>(&())(data));
     }
     _asset_size::<F>();
     Some(f::<F>)
@@ -197,11 +244,9 @@ pub fn _load_module_complete_fn<F: Fn(&mut VM, &str, ffi::WrenLoadModuleResult)>
         name: *const c_char,
         result: ffi::WrenLoadModuleResult,
     ) {
-        mem::transmute::<&(), &F>(&())(
-            &mut VM::from_ptr(vm),
-            CStr::from_ptr(name).to_str().unwrap(),
-            result,
-        );
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+            mem::transmute::<&(), &F>(&())(&mut VM::from_ptr(vm), &to_str(name), result);
+        }));
     }
     _asset_size::<F>();
     Some(f::<F>)
@@ -221,13 +266,16 @@ pub fn _bind_foreign_method_fn<
         is_static: c_int,
         signature: *const c_char,
     ) -> ffi::WrenForeignMethodFn {
-        mem::transmute::<&(), &F>(&())(
-            &mut VM::from_ptr(vm),
-            CStr::from_ptr(module).to_str().unwrap(),
-            CStr::from_ptr(class_name).to_str().unwrap(),
-            is_static != 0,
-            CStr::from_ptr(signature).to_str().unwrap(),
-        )
+        panic::catch_unwind(|| {
+            mem::transmute::<&(), &F>(&())(
+                &mut VM::from_ptr(vm),
+                &to_str(module),
+                &to_str(class_name),
+                is_static != 0,
+                &to_str(signature),
+            )
+        })
+        .unwrap_or(None)
     }
     _asset_size::<F>();
     Some(f::<F>)
@@ -243,12 +291,90 @@ pub fn _bind_foreign_class_fn<F: Fn(&mut VM, &str, &str) -> ffi::WrenForeignClas
         module: *const c_char,
         class_name: *const c_char,
     ) -> ffi::WrenForeignClassMethods {
-        mem::transmute::<&(), &F>(&())(
-            &mut VM::from_ptr(vm),
-            CStr::from_ptr(module).to_str().unwrap(),
-            CStr::from_ptr(class_name).to_str().unwrap(),
-        )
+        panic::catch_unwind(|| {
+            mem::transmute::<&(), &F>(&())(
+                &mut VM::from_ptr(vm),
+                &to_str(module),
+                &to_str(class_name),
+            )
+        })
+        .unwrap_or(ffi::WrenForeignClassMethods {
+            allocate: None,
+            finalize: None,
+        })
     }
     _asset_size::<F>();
     Some(f::<F>)
 }
+
+/// How many closures can be bound to one VM, the size of the dispatch table.
+pub const MAX_FOREIGN_METHODS: usize = 256;
+
+// Wren hands a foreign method nothing but the VM, so each closure bound to a
+// VM gets its own monomorphized trampoline that knows its slot in the VM's
+// dispatch table.
+unsafe extern "C" fn _dispatch<const N: usize>(vm: *mut ffi::WrenVM) {
+    let mut vm = VM::from_ptr(vm);
+    match vm.take_method(N) {
+        Ok(mut method) => {
+            call_foreign(&mut vm, |vm| method(vm));
+            vm.restore_method(N, method);
+        }
+        // Either a slot of this VM that is free, or a closure of another VM
+        // whose table is bigger.
+        Err(message) => {
+            vm.ensure_slots(1);
+            vm.set_slot_string(0, message);
+            vm.abort_fiber(0);
+        }
+    }
+}
+
+macro_rules! dispatch_row {
+    ($row:literal) => {
+        [
+            Some(_dispatch::<{ $row * 16 }>),
+            Some(_dispatch::<{ $row * 16 + 1 }>),
+            Some(_dispatch::<{ $row * 16 + 2 }>),
+            Some(_dispatch::<{ $row * 16 + 3 }>),
+            Some(_dispatch::<{ $row * 16 + 4 }>),
+            Some(_dispatch::<{ $row * 16 + 5 }>),
+            Some(_dispatch::<{ $row * 16 + 6 }>),
+            Some(_dispatch::<{ $row * 16 + 7 }>),
+            Some(_dispatch::<{ $row * 16 + 8 }>),
+            Some(_dispatch::<{ $row * 16 + 9 }>),
+            Some(_dispatch::<{ $row * 16 + 10 }>),
+            Some(_dispatch::<{ $row * 16 + 11 }>),
+            Some(_dispatch::<{ $row * 16 + 12 }>),
+            Some(_dispatch::<{ $row * 16 + 13 }>),
+            Some(_dispatch::<{ $row * 16 + 14 }>),
+            Some(_dispatch::<{ $row * 16 + 15 }>),
+        ]
+    };
+}
+
+#[allow(clippy::erasing_op, clippy::identity_op)]
+static DISPATCH: [[ffi::WrenForeignMethodFn; 16]; 16] = [
+    dispatch_row!(0),
+    dispatch_row!(1),
+    dispatch_row!(2),
+    dispatch_row!(3),
+    dispatch_row!(4),
+    dispatch_row!(5),
+    dispatch_row!(6),
+    dispatch_row!(7),
+    dispatch_row!(8),
+    dispatch_row!(9),
+    dispatch_row!(10),
+    dispatch_row!(11),
+    dispatch_row!(12),
+    dispatch_row!(13),
+    dispatch_row!(14),
+    dispatch_row!(15),
+];
+
+#[doc(hidden)]
+#[inline]
+pub(crate) fn _closure_fn(index: usize) -> ffi::WrenForeignMethodFn {
+    DISPATCH[index / 16][index % 16]
+}
@@ -0,0 +1,127 @@
+use libc::c_void;
+use std::cell::Cell;
+use std::ptr;
+use std::time::Duration;
+
+use crate::ffi;
+use crate::vm::VmData;
+use crate::VM;
+
+// wren's reallocate_fn is never told the old size of a block, so every block
+// starts with a header that remembers it. 16 bytes keep the payload aligned
+// the way malloc's result is.
+const HEADER: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryStats {
+    pub current: usize,
+    pub peak: usize,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GcStats {
+    pub before: usize,
+    pub after: usize,
+    pub duration: Duration,
+}
+
+impl GcStats {
+    pub fn freed(&self) -> usize {
+        self.before.saturating_sub(self.after)
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Tracker {
+    current: Cell<usize>,
+    peak: Cell<usize>,
+    pub(crate) limit: Cell<Option<usize>>,
+    pub(crate) reallocate_fn: ffi::WrenReallocateFn,
+}
+
+impl Tracker {
+    fn record(&self, old_size: usize, new_size: usize) {
+        let current = self.current.get() - old_size + new_size;
+        self.current.set(current);
+        if current > self.peak.get() {
+            self.peak.set(current);
+        }
+    }
+
+    fn allows(&self, old_size: usize, new_size: usize) -> bool {
+        match self.limit.get() {
+            Some(limit) => self.current.get() - old_size + new_size <= limit,
+            None => true,
+        }
+    }
+
+    pub(crate) fn stats(&self) -> MemoryStats {
+        MemoryStats {
+            current: self.current.get(),
+            peak: self.peak.get(),
+            limit: self.limit.get(),
+        }
+    }
+}
+
+// Installed as the reallocate_fn of every VM, with the VM's data as user
+// data. An allocation that would go over the limit fails when wren can take
+// it, which aborts the running fiber. The ones it can't take, made while
+// compiling the source passed to `interpret` or by foreign methods, still go
+// through.
+pub(crate) unsafe extern "C" fn reallocate(
+    memory: *mut c_void,
+    new_size: usize,
+    user_data: *mut c_void,
+) -> *mut c_void {
+    let data = &*(user_data as *const VmData);
+    let tracker = &data.memory;
+    let (block, old_size) = if memory.is_null() {
+        (ptr::null_mut(), 0)
+    } else {
+        let block = (memory as *mut u8).sub(HEADER) as *mut c_void;
+        (block, *(block as *const usize))
+    };
+
+    let raw = |block: *mut c_void, size: usize| match tracker.reallocate_fn {
+        Some(reallocate) => reallocate(block, size, data.user_data),
+        None if size == 0 => {
+            libc::free(block);
+            ptr::null_mut()
+        }
+        None => libc::realloc(block, size),
+    };
+
+    if new_size == 0 {
+        if !block.is_null() {
+            raw(block, 0);
+            tracker.record(old_size, 0);
+        }
+        return ptr::null_mut();
+    }
+    let vm = data.raw();
+    if new_size > old_size
+        && !tracker.allows(old_size, new_size)
+        && !vm.is_null()
+        && ffi::wrenAllocationMayFail(vm)
+    {
+        return ptr::null_mut();
+    }
+    let block = raw(block, new_size + HEADER);
+    if block.is_null() {
+        return ptr::null_mut();
+    }
+    *(block as *mut usize) = new_size;
+    tracker.record(old_size, new_size);
+    (block as *mut u8).add(HEADER) as *mut c_void
+}
+
+impl VM {
+    pub fn memory_stats(&mut self) -> MemoryStats {
+        self.data().memory.stats()
+    }
+    pub fn set_memory_limit(&mut self, limit: Option<usize>) {
+        self.data().memory.limit.set(limit);
+    }
+}
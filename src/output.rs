@@ -0,0 +1,51 @@
+use std::io::{LineWriter, Write};
+use std::mem;
+
+use crate::VM;
+
+// Where `System.print` and friends end up when the VM was configured with
+// one of the output options rather than a raw write_fn.
+pub(crate) enum Output {
+    Capture(String),
+    Writer(Box<dyn Write>),
+    Lines(LineWriter<Box<dyn Write>>),
+}
+
+impl Output {
+    fn write(&mut self, text: &str) {
+        // wren has no way to hear about a failed write, so errors are dropped
+        // just like print! would lose them on a closed stdout.
+        let _ = match self {
+            Output::Capture(buffer) => {
+                buffer.push_str(text);
+                Ok(())
+            }
+            Output::Writer(writer) => writer.write_all(text.as_bytes()),
+            Output::Lines(writer) => writer.write_all(text.as_bytes()),
+        };
+    }
+}
+
+pub(crate) fn write_output(vm: &mut VM, text: &str) {
+    if let Some(output) = &mut vm.data().output {
+        output.write(text);
+    }
+}
+
+impl VM {
+    /// Everything written since the last call, when the VM was configured
+    /// with `capture_output`.
+    pub fn take_output(&mut self) -> String {
+        match &mut self.data().output {
+            Some(Output::Capture(buffer)) => mem::take(buffer),
+            _ => String::new(),
+        }
+    }
+    pub fn flush_output(&mut self) {
+        let _ = match &mut self.data().output {
+            Some(Output::Writer(writer)) => writer.flush(),
+            Some(Output::Lines(writer)) => writer.flush(),
+            _ => Ok(()),
+        };
+    }
+}
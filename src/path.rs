@@ -0,0 +1,188 @@
+// Module paths, following the semantics of wren_cli's path.c. Paths are plain
+// strings since they double as module names and have to look the same on
+// every platform.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathType {
+    Absolute,
+    Relative,
+    Simple,
+}
+
+#[inline]
+fn is_separator(c: u8) -> bool {
+    c == b'/' || (cfg!(target_os = "windows") && c == b'\\')
+}
+
+fn absolute_prefix_length(path: &str) -> usize {
+    let bytes = path.as_bytes();
+    if cfg!(target_os = "windows")
+        && bytes.len() >= 2
+        && bytes[0].is_ascii_alphabetic()
+        && bytes[1] == b':'
+    {
+        // "C:" on its own is relative to the current directory on that drive,
+        // but it's absolute as far as imports are concerned.
+        return if bytes.len() >= 3 && is_separator(bytes[2]) {
+            3
+        } else {
+            2
+        };
+    }
+    match bytes.first() {
+        Some(&c) if is_separator(c) => 1,
+        _ => 0,
+    }
+}
+
+pub fn path_type(path: &str) -> PathType {
+    if absolute_prefix_length(path) > 0 {
+        return PathType::Absolute;
+    }
+    let bytes = path.as_bytes();
+    let at = |i: usize| bytes.get(i).copied().unwrap_or(0);
+    if (at(0) == b'.' && is_separator(at(1)))
+        || (at(0) == b'.' && at(1) == b'.' && is_separator(at(2)))
+    {
+        return PathType::Relative;
+    }
+    PathType::Simple
+}
+
+/// Everything up to the last separator, or "" if there is none.
+pub fn dir_name(path: &str) -> &str {
+    match path.bytes().rposition(is_separator) {
+        Some(i) => &path[..i],
+        None => "",
+    }
+}
+
+pub fn join(path: &str, other: &str) -> String {
+    let mut joined = path.to_string();
+    if !joined.is_empty() && !joined.bytes().last().is_some_and(is_separator) {
+        joined.push('/');
+    }
+    joined.push_str(other);
+    joined
+}
+
+pub fn remove_extension(path: &str) -> &str {
+    for (i, c) in path.bytes().enumerate().rev() {
+        if is_separator(c) {
+            break;
+        }
+        if c == b'.' {
+            return &path[..i];
+        }
+    }
+    path
+}
+
+/// Collapses "." and "..", and repeated separators. The kind of path is kept,
+/// so "./a/../b" stays relative as "./b" instead of becoming the logical "b".
+pub fn normalize(path: &str) -> String {
+    let prefix = absolute_prefix_length(path);
+    let mut components: Vec<&str> = Vec::new();
+    let mut leading_doubles = 0;
+    for component in path[prefix..].split(|c: char| c.is_ascii() && is_separator(c as u8)) {
+        match component {
+            "" | "." => {}
+            ".." => {
+                if components.pop().is_none() {
+                    leading_doubles += 1;
+                }
+            }
+            _ => components.push(component),
+        }
+    }
+
+    let mut result = String::new();
+    let mut parts: Vec<&str> = Vec::new();
+    if prefix > 0 {
+        result.push_str(&path[..prefix]);
+    } else if leading_doubles > 0 {
+        parts.extend(std::iter::repeat_n("..", leading_doubles));
+    } else if path_type(path) == PathType::Relative {
+        parts.push(".");
+    }
+    parts.extend(components);
+    result.push_str(&parts.join("/"));
+    if result.is_empty() {
+        result.push('.');
+    }
+    result
+}
+
+/// Resolves `name` as imported from the module `importer`. Logical imports are
+/// left alone, relative ones are taken from the importer's directory.
+pub fn resolve(importer: &str, name: &str) -> String {
+    if path_type(name) == PathType::Simple {
+        return name.to_string();
+    }
+    normalize(&join(dir_name(importer), name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_types() {
+        assert_eq!(path_type("a"), PathType::Simple);
+        assert_eq!(path_type(".a"), PathType::Simple);
+        assert_eq!(path_type("..a"), PathType::Simple);
+        assert_eq!(path_type(""), PathType::Simple);
+        assert_eq!(path_type("./a"), PathType::Relative);
+        assert_eq!(path_type("../a"), PathType::Relative);
+        assert_eq!(path_type("/a"), PathType::Absolute);
+    }
+
+    #[test]
+    fn dir_names_and_joins() {
+        assert_eq!(dir_name("a/b/c"), "a/b");
+        assert_eq!(dir_name("main"), "");
+        assert_eq!(dir_name("/main"), "");
+        assert_eq!(join("", "a"), "a");
+        assert_eq!(join("a", "b"), "a/b");
+        assert_eq!(join("a/", "b"), "a/b");
+    }
+
+    #[test]
+    fn extensions() {
+        assert_eq!(remove_extension("main.wren"), "main");
+        assert_eq!(remove_extension("a/main.x.wren"), "a/main.x");
+        assert_eq!(remove_extension("lib.d/file"), "lib.d/file");
+        assert_eq!(remove_extension("file"), "file");
+    }
+
+    #[test]
+    fn normalizing() {
+        assert_eq!(normalize(""), ".");
+        assert_eq!(normalize("."), ".");
+        assert_eq!(normalize("./a/../b"), "./b");
+        assert_eq!(normalize("./a/.."), ".");
+        assert_eq!(normalize("a//b/./c/.."), "a/b");
+        assert_eq!(normalize("../../a/.."), "../..");
+        assert_eq!(normalize("/../a"), "/a");
+        assert_eq!(normalize("/"), "/");
+    }
+
+    #[test]
+    fn resolving() {
+        assert_eq!(resolve("main", "./lib"), "./lib");
+        assert_eq!(resolve("./src/main", "../lib/./x"), "./lib/x");
+        assert_eq!(resolve("./src/main", "../../x"), "../x");
+        assert_eq!(resolve("./src/main", "json"), "json");
+        assert_eq!(resolve("/app/main", "./lib"), "/app/lib");
+    }
+
+    #[cfg(target_os = "windows")]
+    #[test]
+    fn windows_paths() {
+        assert_eq!(path_type("C:\\a"), PathType::Absolute);
+        assert_eq!(path_type("C:a"), PathType::Absolute);
+        assert_eq!(path_type(".\\a"), PathType::Relative);
+        assert_eq!(normalize("C:\\a\\..\\b"), "C:\\b");
+        assert_eq!(resolve("C:\\src\\main", ".\\lib"), "C:\\src/lib");
+    }
+}
@@ -0,0 +1,211 @@
+use std::collections::BTreeMap;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::vm::{Configuration, Handle};
+use crate::{Error, VM};
+
+// The "scheduler" and "timer" modules of wren_cli, so scripts written for it
+// run unchanged. Fibers are resumed by the host instead of a libuv loop.
+const SCHEDULER: &str = "class Scheduler {
+  static add(callable) {
+    if (__scheduled == null) __scheduled = []
+
+    __scheduled.add(Fiber.new {
+      callable.call()
+      runNextScheduled_()
+    })
+  }
+
+  // Waits for a method that resumes the current fiber from the host.
+  static await_(fn) {
+    fn.call()
+    return Scheduler.runNextScheduled_()
+  }
+
+  static runNextScheduled_() {
+    if (__scheduled == null || __scheduled.isEmpty) {
+      return Fiber.suspend()
+    } else {
+      return __scheduled.removeAt(0).transfer()
+    }
+  }
+}
+";
+
+const TIMER: &str = "import \"scheduler\" for Scheduler
+
+class Timer {
+  static sleep(milliseconds) {
+    if (!(milliseconds is Num)) Fiber.abort(\"Milliseconds must be a number.\")
+    if (milliseconds < 0) Fiber.abort(\"Milliseconds cannot be negative.\")
+
+    return Scheduler.await_ { startTimer_(milliseconds, Fiber.current) }
+  }
+
+  foreign static startTimer_(milliseconds, fiber)
+}
+";
+
+pub(crate) fn module(name: &str) -> Option<&'static str> {
+    match name {
+        "scheduler" => Some(SCHEDULER),
+        "timer" => Some(TIMER),
+        _ => None,
+    }
+}
+
+impl Configuration {
+    /// Makes the "scheduler" and "timer" modules importable. They come after
+    /// the module loader, which can still replace them.
+    pub fn enable_scheduler(&mut self) {
+        self.scheduler = true;
+        self.register_method("timer", "Timer", true, "startTimer_(_,_)", start_timer);
+    }
+}
+
+fn start_timer(vm: &mut VM) {
+    let milliseconds = vm.get_slot_double(1).unwrap_or(0.0);
+    // Duration can't hold an infinite or NaN delay.
+    if !milliseconds.is_finite() {
+        vm.set_slot_string(0, "Milliseconds must be finite.");
+        vm.abort_fiber(0);
+        return;
+    }
+    let timers = &vm.data().timers;
+    let deadline = Duration::try_from_secs_f64(milliseconds / 1000.0)
+        .ok()
+        .and_then(|delay| timers.clock.now().checked_add(delay));
+    let Some(deadline) = deadline else {
+        vm.set_slot_string(0, "Milliseconds is too large.");
+        vm.abort_fiber(0);
+        return;
+    };
+    let fiber = vm.get_slot_handle(2);
+    let timers = &mut vm.data().timers;
+    timers.next_id += 1;
+    timers.queue.insert((deadline, timers.next_id), fiber);
+}
+
+// Time since the VM was created. A virtual clock only moves when the host
+// advances it, which makes scripts that sleep deterministic.
+pub(crate) enum Clock {
+    Real(Instant),
+    Virtual(Duration),
+}
+
+impl Clock {
+    fn now(&self) -> Duration {
+        match self {
+            Clock::Real(start) => start.elapsed(),
+            Clock::Virtual(now) => *now,
+        }
+    }
+}
+
+// Sleeping fibers by deadline, the ones that went to sleep first go first
+// when the deadlines are equal.
+pub(crate) struct Timers {
+    pub(crate) clock: Clock,
+    queue: BTreeMap<(Duration, u64), Handle>,
+    next_id: u64,
+}
+
+impl Timers {
+    pub(crate) fn new() -> Timers {
+        Timers {
+            clock: Clock::Real(Instant::now()),
+            queue: BTreeMap::new(),
+            next_id: 0,
+        }
+    }
+    fn next_deadline(&self) -> Option<Duration> {
+        self.queue.keys().next().map(|(deadline, _)| *deadline)
+    }
+}
+
+impl VM {
+    pub fn pending_timers(&mut self) -> usize {
+        self.data().timers.queue.len()
+    }
+    /// How long until the next sleeping fiber is due, zero if one already is.
+    pub fn next_timer(&mut self) -> Option<Duration> {
+        let timers = &self.data().timers;
+        let deadline = timers.next_deadline()?;
+        Some(deadline.saturating_sub(timers.clock.now()))
+    }
+    /// Resumes every fiber whose sleep is over and returns how many there were.
+    /// Fibers that go to sleep again in the meantime wait for the next call,
+    /// even with a zero delay. When a fiber fails the others are still
+    /// resumed, and the first error is returned afterwards.
+    pub fn fire_timers(&mut self) -> Result<usize, Error> {
+        let mut first_error = None;
+        let count = self.fire_due(&mut first_error);
+        match first_error {
+            Some(error) => Err(error),
+            None => Ok(count),
+        }
+    }
+    fn fire_due(&mut self, first_error: &mut Option<Error>) -> usize {
+        let now = self.data().timers.clock.now();
+        let due = {
+            let queue = &mut self.data().timers.queue;
+            let later = queue.split_off(&(now, u64::MAX));
+            std::mem::replace(queue, later)
+        };
+        let count = due.len();
+        for fiber in due.into_values() {
+            if let Err(error) = self.call_method::<(), _>(&fiber, "transfer", ()) {
+                first_error.get_or_insert(error);
+            }
+        }
+        count
+    }
+    /// Moves a virtual clock forward, resuming the fibers that wake up on the
+    /// way at the time they asked for. Like `fire_timers` it carries on past a
+    /// failing fiber, the clock ends up at its target either way.
+    pub fn advance_clock(&mut self, by: Duration) -> Result<usize, Error> {
+        let target = match self.data().timers.clock {
+            Clock::Virtual(now) => now + by,
+            Clock::Real(_) => return Err(Error::RealClock),
+        };
+        let mut count = 0;
+        let mut first_error = None;
+        while let Some(deadline) = self.data().timers.next_deadline() {
+            if deadline > target {
+                break;
+            }
+            self.set_virtual_now(deadline);
+            count += self.fire_due(&mut first_error);
+        }
+        self.set_virtual_now(target);
+        match first_error {
+            Some(error) => Err(error),
+            None => Ok(count),
+        }
+    }
+    /// Runs until no fiber is sleeping any more. A real clock is waited for,
+    /// a virtual one jumps straight to the next deadline. The first error is
+    /// returned once every fiber is done.
+    pub fn run_timers(&mut self) -> Result<(), Error> {
+        let mut first_error = None;
+        while let Some(wait) = self.next_timer() {
+            if let Clock::Virtual(now) = self.data().timers.clock {
+                self.set_virtual_now(now + wait);
+            } else if !wait.is_zero() {
+                thread::sleep(wait);
+            }
+            self.fire_due(&mut first_error);
+        }
+        match first_error {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+    fn set_virtual_now(&mut self, now: Duration) {
+        let clock = &mut self.data().timers.clock;
+        if let Clock::Virtual(current) = clock {
+            *current = (*current).max(now);
+        }
+    }
+}
@@ -0,0 +1,753 @@
+use serde::de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor};
+use serde::ser::{self, Serialize};
+use std::fmt::Display;
+use std::str;
+
+use crate::{Error, Type, VM};
+
+impl VM {
+    pub fn set_serialize<T: Serialize + ?Sized>(
+        &mut self,
+        slot: i32,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.restoring_slots(|vm| {
+            value.serialize(Serializer {
+                vm,
+                slot,
+                path: String::new(),
+            })
+        })
+    }
+    pub fn get_deserialize<T: DeserializeOwned>(&mut self, slot: i32) -> Result<T, Error> {
+        self.restoring_slots(|vm| {
+            T::deserialize(Deserializer {
+                vm,
+                slot,
+                path: String::new(),
+            })
+        })
+    }
+}
+
+impl ser::Error for Error {
+    fn custom<T: Display>(message: T) -> Error {
+        Error::Serde {
+            path: String::new(),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: Display>(message: T) -> Error {
+        Error::Serde {
+            path: String::new(),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl Error {
+    // Errors are raised without knowing where they happened, the innermost
+    // (de)serializer that sees one fills in its path.
+    fn at(self, at: &str) -> Error {
+        match self {
+            Error::Serde { path, message } if path.is_empty() => Error::Serde {
+                path: at.to_string(),
+                message,
+            },
+            error => error,
+        }
+    }
+}
+
+fn field_path(path: &str, field: &str) -> String {
+    if path.is_empty() {
+        field.to_string()
+    } else {
+        format!("{}.{}", path, field)
+    }
+}
+
+fn index_path(path: &str, index: impl Display) -> String {
+    format!("{}[{}]", path, index)
+}
+
+// Map keys are hashed by wren, which only works for value types.
+fn check_key(vm: &mut VM, slot: i32) -> Result<(), Error> {
+    match vm.get_slot_type(slot) {
+        Type::Bool | Type::Num | Type::String | Type::Null => Ok(()),
+        found => Err(ser::Error::custom(format!(
+            "map keys must be strings, numbers, booleans or null, not {:?}",
+            found
+        ))),
+    }
+}
+
+pub struct Serializer<'a> {
+    vm: &'a mut VM,
+    slot: i32,
+    path: String,
+}
+
+impl<'a> Serializer<'a> {
+    fn set<F: FnOnce(&mut VM, i32)>(self, f: F) -> Result<(), Error> {
+        f(self.vm, self.slot);
+        Ok(())
+    }
+
+    // A single entry map, `{variant: value}`, with the value written by
+    // whatever follows.
+    fn tagged(&mut self, variant: &str) -> i32 {
+        let key = self.vm.scratch_slots(2);
+        self.vm.set_slot_new_map(self.slot);
+        self.vm.set_slot_string(key, variant);
+        self.path = field_path(&self.path, variant);
+        key
+    }
+
+    fn list(self, tag: Option<i32>) -> Result<ListSerializer<'a>, Error> {
+        let element = self.vm.scratch_slots(1);
+        let list = match tag {
+            Some(key) => key + 1,
+            None => self.slot,
+        };
+        self.vm.set_slot_new_list(list);
+        Ok(ListSerializer {
+            vm: self.vm,
+            slot: self.slot,
+            list,
+            tag,
+            element,
+            path: self.path,
+            index: 0,
+        })
+    }
+
+    fn map(self, tag: Option<i32>) -> Result<MapSerializer<'a>, Error> {
+        let key = self.vm.scratch_slots(2);
+        let map = match tag {
+            Some(key) => key + 1,
+            None => self.slot,
+        };
+        self.vm.set_slot_new_map(map);
+        Ok(MapSerializer {
+            vm: self.vm,
+            slot: self.slot,
+            map,
+            tag,
+            key,
+            path: self.path,
+            key_path: String::new(),
+        })
+    }
+}
+
+impl<'a> ser::Serializer for Serializer<'a> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = ListSerializer<'a>;
+    type SerializeTuple = ListSerializer<'a>;
+    type SerializeTupleStruct = ListSerializer<'a>;
+    type SerializeTupleVariant = ListSerializer<'a>;
+    type SerializeMap = MapSerializer<'a>;
+    type SerializeStruct = MapSerializer<'a>;
+    type SerializeStructVariant = MapSerializer<'a>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), Error> {
+        self.set(|vm, slot| vm.set_slot_bool(slot, v))
+    }
+    fn serialize_i8(self, v: i8) -> Result<(), Error> {
+        self.serialize_f64(v as f64)
+    }
+    fn serialize_i16(self, v: i16) -> Result<(), Error> {
+        self.serialize_f64(v as f64)
+    }
+    fn serialize_i32(self, v: i32) -> Result<(), Error> {
+        self.serialize_f64(v as f64)
+    }
+    fn serialize_i64(self, v: i64) -> Result<(), Error> {
+        self.serialize_f64(v as f64)
+    }
+    fn serialize_u8(self, v: u8) -> Result<(), Error> {
+        self.serialize_f64(v as f64)
+    }
+    fn serialize_u16(self, v: u16) -> Result<(), Error> {
+        self.serialize_f64(v as f64)
+    }
+    fn serialize_u32(self, v: u32) -> Result<(), Error> {
+        self.serialize_f64(v as f64)
+    }
+    fn serialize_u64(self, v: u64) -> Result<(), Error> {
+        self.serialize_f64(v as f64)
+    }
+    fn serialize_f32(self, v: f32) -> Result<(), Error> {
+        self.serialize_f64(v as f64)
+    }
+    fn serialize_f64(self, v: f64) -> Result<(), Error> {
+        self.set(|vm, slot| vm.set_slot_double(slot, v))
+    }
+    fn serialize_char(self, v: char) -> Result<(), Error> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+    fn serialize_str(self, v: &str) -> Result<(), Error> {
+        self.set(|vm, slot| vm.set_slot_bytes(slot, v.as_bytes()))
+    }
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), Error> {
+        self.set(|vm, slot| vm.set_slot_bytes(slot, v))
+    }
+    fn serialize_none(self) -> Result<(), Error> {
+        self.serialize_unit()
+    }
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+    fn serialize_unit(self) -> Result<(), Error> {
+        self.set(|vm, slot| vm.set_slot_null(slot))
+    }
+    fn serialize_unit_struct(self, _: &'static str) -> Result<(), Error> {
+        self.serialize_unit()
+    }
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        self.serialize_str(variant)
+    }
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        value.serialize(self)
+    }
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        mut self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        let key = self.tagged(variant);
+        value.serialize(Serializer {
+            vm: self.vm,
+            slot: key + 1,
+            path: self.path,
+        })?;
+        self.vm.set_map_value(self.slot, key, key + 1);
+        Ok(())
+    }
+    fn serialize_seq(self, _: Option<usize>) -> Result<ListSerializer<'a>, Error> {
+        self.list(None)
+    }
+    fn serialize_tuple(self, _: usize) -> Result<ListSerializer<'a>, Error> {
+        self.list(None)
+    }
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> Result<ListSerializer<'a>, Error> {
+        self.list(None)
+    }
+    fn serialize_tuple_variant(
+        mut self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        _: usize,
+    ) -> Result<ListSerializer<'a>, Error> {
+        let key = self.tagged(variant);
+        self.list(Some(key))
+    }
+    fn serialize_map(self, _: Option<usize>) -> Result<MapSerializer<'a>, Error> {
+        self.map(None)
+    }
+    fn serialize_struct(self, _: &'static str, _: usize) -> Result<MapSerializer<'a>, Error> {
+        self.map(None)
+    }
+    fn serialize_struct_variant(
+        mut self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        _: usize,
+    ) -> Result<MapSerializer<'a>, Error> {
+        let key = self.tagged(variant);
+        self.map(Some(key))
+    }
+}
+
+pub struct ListSerializer<'a> {
+    vm: &'a mut VM,
+    slot: i32,
+    list: i32,
+    // The key slot of the `{variant: [...]}` map for tuple variants.
+    tag: Option<i32>,
+    element: i32,
+    path: String,
+    index: usize,
+}
+
+impl ListSerializer<'_> {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let path = index_path(&self.path, self.index);
+        value
+            .serialize(Serializer {
+                vm: self.vm,
+                slot: self.element,
+                path: path.clone(),
+            })
+            .map_err(|error| error.at(&path))?;
+        self.vm.list_push(self.list, self.element);
+        self.index += 1;
+        Ok(())
+    }
+    fn finish(self) -> Result<(), Error> {
+        if let Some(key) = self.tag {
+            self.vm.set_map_value(self.slot, key, self.list);
+        }
+        Ok(())
+    }
+}
+
+impl ser::SerializeSeq for ListSerializer<'_> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for ListSerializer<'_> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for ListSerializer<'_> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for ListSerializer<'_> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+pub struct MapSerializer<'a> {
+    vm: &'a mut VM,
+    slot: i32,
+    map: i32,
+    tag: Option<i32>,
+    // Keys go in `key`, values in `key + 1`.
+    key: i32,
+    path: String,
+    key_path: String,
+}
+
+impl MapSerializer<'_> {
+    fn put_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        key.serialize(Serializer {
+            vm: self.vm,
+            slot: self.key,
+            path: self.path.clone(),
+        })?;
+        check_key(self.vm, self.key).map_err(|error| error.at(&self.path))?;
+        self.key_path = match self.vm.get_slot_type(self.key) {
+            Type::String => {
+                let key = self.vm.get::<String>(self.key)?;
+                field_path(&self.path, &key)
+            }
+            _ => index_path(&self.path, self.vm.get_slot_double(self.key).unwrap_or(0.0)),
+        };
+        Ok(())
+    }
+    fn put_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        value
+            .serialize(Serializer {
+                vm: self.vm,
+                slot: self.key + 1,
+                path: self.key_path.clone(),
+            })
+            .map_err(|error| error.at(&self.key_path))?;
+        self.vm.set_map_value(self.map, self.key, self.key + 1);
+        Ok(())
+    }
+    fn finish(self) -> Result<(), Error> {
+        if let Some(key) = self.tag {
+            self.vm.set_map_value(self.slot, key, self.map);
+        }
+        Ok(())
+    }
+}
+
+impl ser::SerializeMap for MapSerializer<'_> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        self.put_key(key)
+    }
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.put_value(value)
+    }
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for MapSerializer<'_> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.put_key(key)?;
+        self.put_value(value)
+    }
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for MapSerializer<'_> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.put_key(key)?;
+        self.put_value(value)
+    }
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+pub struct Deserializer<'a> {
+    vm: &'a mut VM,
+    slot: i32,
+    path: String,
+}
+
+impl<'a> Deserializer<'a> {
+    fn string(&mut self) -> Result<String, Error> {
+        let bytes = self.vm.get_slot_bytes(self.slot).unwrap_or_default();
+        match str::from_utf8(bytes) {
+            Ok(s) => Ok(s.to_string()),
+            Err(error) => {
+                let error: Error =
+                    de::Error::custom(format!("string is not valid UTF-8: {}", error));
+                Err(error.at(&self.path))
+            }
+        }
+    }
+
+    fn visit<'de, V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Error> {
+        let result = match self.vm.get_slot_type(self.slot) {
+            Type::Bool => visitor.visit_bool(self.vm.get_slot_bool(self.slot).unwrap()),
+            Type::Num => {
+                let value = self.vm.get_slot_double(self.slot).unwrap();
+                // Integral numbers are handed out as integers, so they can
+                // end up in integer fields.
+                if value.fract() == 0.0 && value >= i64::MIN as f64 && value < i64::MAX as f64 {
+                    visitor.visit_i64(value as i64)
+                } else if value.fract() == 0.0 && value >= 0.0 && value < u64::MAX as f64 {
+                    visitor.visit_u64(value as u64)
+                } else {
+                    visitor.visit_f64(value)
+                }
+            }
+            Type::String => {
+                let string = self.string()?;
+                visitor.visit_string(string)
+            }
+            Type::Null => visitor.visit_unit(),
+            Type::List => {
+                let count = self.vm.get_list_count(self.slot);
+                let element = self.vm.scratch_slots(1);
+                visitor.visit_seq(ListAccess {
+                    vm: self.vm,
+                    list: self.slot,
+                    element,
+                    path: &self.path,
+                    index: 0,
+                    count,
+                })
+            }
+            Type::Map => {
+                let keys = self.vm.scratch_slots(3);
+                self.vm.map_keys(self.slot, keys)?;
+                let count = self.vm.get_list_count(keys);
+                visitor.visit_map(MapAccess {
+                    vm: self.vm,
+                    map: self.slot,
+                    keys,
+                    path: &self.path,
+                    key_path: String::new(),
+                    index: 0,
+                    count,
+                })
+            }
+            found => Err(de::Error::custom(format!(
+                "cannot deserialize a {:?} value",
+                found
+            ))),
+        };
+        result.map_err(|error| error.at(&self.path))
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer<'_> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.visit(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.vm.get_slot_type(self.slot) == Type::String {
+            let bytes = self.vm.get_slot_bytes(self.slot).unwrap_or_default();
+            return visitor
+                .visit_bytes::<Error>(bytes)
+                .map_err(|error| error.at(&self.path));
+        }
+        self.visit(visitor)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.vm.get_slot_type(self.slot) == Type::Null {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    // Unit variants are plain strings, the others maps with a single entry
+    // from the variant name to its contents.
+    fn deserialize_enum<V: Visitor<'de>>(
+        mut self,
+        _: &'static str,
+        _: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let result = match self.vm.get_slot_type(self.slot) {
+            Type::String => {
+                let variant = self.string()?;
+                visitor.visit_enum(variant.into_deserializer())
+            }
+            Type::Map if self.vm.get_map_count(self.slot) == 1 => {
+                let keys = self.vm.scratch_slots(3);
+                self.vm.map_keys(self.slot, keys)?;
+                self.vm.list_get(keys, 0, keys + 1);
+                self.vm.get_map_value(self.slot, keys + 1, keys + 2);
+                let variant = Deserializer {
+                    vm: self.vm,
+                    slot: keys + 1,
+                    path: self.path.clone(),
+                }
+                .string()?;
+                visitor.visit_enum(EnumAccess {
+                    vm: self.vm,
+                    value: keys + 2,
+                    path: field_path(&self.path, &variant),
+                    variant,
+                })
+            }
+            found => Err(de::Error::custom(format!(
+                "expected a variant name or a map with a single entry, found {:?}",
+                found
+            ))),
+        };
+        result.map_err(|error| error.at(&self.path))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+struct ListAccess<'a, 'p> {
+    vm: &'a mut VM,
+    list: i32,
+    element: i32,
+    path: &'p str,
+    index: i32,
+    count: i32,
+}
+
+impl<'de> de::SeqAccess<'de> for ListAccess<'_, '_> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        if self.index >= self.count {
+            return Ok(None);
+        }
+        self.vm.list_get(self.list, self.index, self.element);
+        let path = index_path(self.path, self.index);
+        self.index += 1;
+        seed.deserialize(Deserializer {
+            vm: self.vm,
+            slot: self.element,
+            path: path.clone(),
+        })
+        .map(Some)
+        .map_err(|error| error.at(&path))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some((self.count - self.index) as usize)
+    }
+}
+
+struct MapAccess<'a, 'p> {
+    vm: &'a mut VM,
+    map: i32,
+    // The list of keys, followed by slots for the current key and value.
+    keys: i32,
+    path: &'p str,
+    key_path: String,
+    index: i32,
+    count: i32,
+}
+
+impl<'de> de::MapAccess<'de> for MapAccess<'_, '_> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        if self.index >= self.count {
+            return Ok(None);
+        }
+        let key = self.keys + 1;
+        self.vm.list_get(self.keys, self.index, key);
+        self.index += 1;
+        self.key_path = match self.vm.get_slot_type(key) {
+            Type::String => field_path(
+                self.path,
+                &String::from_utf8_lossy(self.vm.get_slot_bytes(key).unwrap_or_default()),
+            ),
+            Type::Num => index_path(self.path, self.vm.get_slot_double(key).unwrap()),
+            _ => index_path(self.path, self.index - 1),
+        };
+        seed.deserialize(Deserializer {
+            vm: self.vm,
+            slot: key,
+            path: self.key_path.clone(),
+        })
+        .map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let (key, value) = (self.keys + 1, self.keys + 2);
+        self.vm.get_map_value(self.map, key, value);
+        seed.deserialize(Deserializer {
+            vm: self.vm,
+            slot: value,
+            path: self.key_path.clone(),
+        })
+        .map_err(|error| error.at(&self.key_path))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some((self.count - self.index) as usize)
+    }
+}
+
+struct EnumAccess<'a> {
+    vm: &'a mut VM,
+    value: i32,
+    variant: String,
+    path: String,
+}
+
+impl<'de, 'a> de::EnumAccess<'de> for EnumAccess<'a> {
+    type Error = Error;
+    type Variant = Deserializer<'a>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Deserializer<'a>), Error> {
+        let variant: de::value::StringDeserializer<Error> = self.variant.into_deserializer();
+        let variant = seed.deserialize(variant)?;
+        Ok((
+            variant,
+            Deserializer {
+                vm: self.vm,
+                slot: self.value,
+                path: self.path,
+            },
+        ))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Deserializer<'_> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        de::Deserialize::deserialize(self)
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        let path = self.path.clone();
+        seed.deserialize(self).map_err(|error| error.at(&path))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _: usize, visitor: V) -> Result<V::Value, Error> {
+        self.visit(visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.visit(visitor)
+    }
+}
@@ -0,0 +1,137 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+
+use crate::convert::ToWren;
+use crate::vm::{Configuration, Handle};
+use crate::{Error, VM};
+
+type Completion = Result<Box<dyn ToWren>, String>;
+
+// A fiber waiting on a future, resumed with its output.
+pub(crate) struct Task {
+    fiber: Handle,
+    future: Pin<Box<dyn Future<Output = Completion>>>,
+}
+
+impl Configuration {
+    /// Registers a foreign method that waits on a future without blocking the
+    /// VM. Like wren_cli's scheduler it relies on the script to hand over the
+    /// fiber and suspend it, so the first argument must be `Fiber.current`:
+    ///
+    /// ```text
+    /// foreign static read_(fiber, path)
+    /// static read(path) {
+    ///   read_(Fiber.current, path)
+    ///   return Fiber.suspend()
+    /// }
+    /// ```
+    ///
+    /// `f` reads the arguments and returns the future. The fiber is resumed
+    /// by `VM::poll` or `VM::run_until_idle` with the output of the future,
+    /// or aborted with the message when it is an `Err`.
+    pub fn register_async_method<F, Fut, T>(
+        &mut self,
+        module: &str,
+        class_name: &str,
+        is_static: bool,
+        signature: &str,
+        mut f: F,
+    ) where
+        F: FnMut(&mut VM) -> Fut + 'static,
+        Fut: Future<Output = Result<T, String>> + 'static,
+        T: ToWren + 'static,
+    {
+        self.register_method(module, class_name, is_static, signature, move |vm| {
+            // Anything but a fiber would never be resumed.
+            if !vm.is_fiber(1) {
+                vm.set_slot_string(0, "Pass Fiber.current as the first argument.");
+                vm.abort_fiber(0);
+                return;
+            }
+            let fiber = vm.get_slot_handle(1);
+            let future = f(vm);
+            let future =
+                async move { future.await.map(|value| Box::new(value) as Box<dyn ToWren>) };
+            vm.data().tasks.push(Task {
+                fiber,
+                future: Box::pin(future),
+            });
+        });
+    }
+}
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+impl VM {
+    pub fn pending_tasks(&mut self) -> usize {
+        self.data().tasks.len()
+    }
+
+    /// Polls every pending future once and resumes the fibers of those that
+    /// are done. Ready once nothing is waiting any more, so an executor can
+    /// drive the VM with `poll_fn(|cx| vm.poll(cx))`. A fiber that fails
+    /// doesn't stop the others from being resumed, the first error is
+    /// returned once the rest are done or pending.
+    pub fn poll(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        let mut first_error = None;
+        loop {
+            let mut pending = Vec::new();
+            let mut ready = Vec::new();
+            for mut task in std::mem::take(&mut self.data().tasks) {
+                match task.future.as_mut().poll(cx) {
+                    Poll::Ready(completion) => ready.push((task.fiber, completion)),
+                    Poll::Pending => pending.push(task),
+                }
+            }
+            self.data().tasks = pending;
+
+            if ready.is_empty() {
+                return match first_error {
+                    Some(error) => Poll::Ready(Err(error)),
+                    None if self.data().tasks.is_empty() => Poll::Ready(Ok(())),
+                    None => Poll::Pending,
+                };
+            }
+            for (fiber, completion) in ready {
+                let result = match completion {
+                    Ok(value) => self.call_method::<(), _>(&fiber, "transfer", (&*value,)),
+                    Err(message) => self.call_method::<(), _>(&fiber, "transferError", (message,)),
+                };
+                if let Err(error) = result {
+                    first_error.get_or_insert(error);
+                }
+            }
+        }
+    }
+
+    /// Blocks the current thread until every parked fiber has been resumed.
+    /// Keeps going after a fiber fails and returns the first error at the end.
+    pub fn run_until_idle(&mut self) -> Result<(), Error> {
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let mut first_error = None;
+        loop {
+            match self.poll(&mut cx) {
+                Poll::Ready(Err(error)) => {
+                    first_error.get_or_insert(error);
+                }
+                Poll::Ready(Ok(())) if self.data().tasks.is_empty() => break,
+                Poll::Ready(Ok(())) => {}
+                Poll::Pending => thread::park(),
+            }
+        }
+        match first_error {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+}
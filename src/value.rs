@@ -0,0 +1,203 @@
+use std::fmt;
+
+use crate::convert::{FromWren, ToWren};
+use crate::vm::Handle;
+use crate::{Error, Type, VM};
+
+// A deep copy of a wren value. Foreign objects and everything that can't be
+// copied, like class instances, functions and ranges, are kept by handle.
+#[derive(Debug)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Num(f64),
+    String(Vec<u8>),
+    List(Vec<Value>),
+    /// wren maps are unordered and can have numbers as keys, so they are kept
+    /// as pairs rather than in a HashMap.
+    Map(Vec<(Value, Value)>),
+    Foreign(Handle),
+    Object(Handle),
+}
+
+impl VM {
+    pub fn get_value(&mut self, slot: i32) -> Result<Value, Error> {
+        self.restoring_slots(|vm| vm.copy_value(slot, &mut Vec::new()))
+    }
+    // `ancestors` holds the lists and maps being copied further up, meeting
+    // one of them again means the value contains itself.
+    fn copy_value(&mut self, slot: i32, ancestors: &mut Vec<u64>) -> Result<Value, Error> {
+        let value = match self.get_slot_type(slot) {
+            Type::Null => Value::Null,
+            Type::Bool => Value::Bool(self.get_slot_bool(slot).unwrap()),
+            Type::Num => Value::Num(self.get_slot_double(slot).unwrap()),
+            Type::String => Value::String(self.get_slot_bytes(slot).unwrap().to_vec()),
+            Type::Foreign => Value::Foreign(self.get_slot_handle(slot)),
+            Type::Unknown => Value::Object(self.get_slot_handle(slot)),
+            Type::List => {
+                self.enter_container(slot, ancestors)?;
+                let element = self.scratch_slots(1);
+                let count = self.get_list_count(slot);
+                let mut list = Vec::with_capacity(count as usize);
+                for index in 0..count {
+                    self.list_get(slot, index, element);
+                    list.push(self.copy_value(element, ancestors)?);
+                }
+                ancestors.pop();
+                Value::List(list)
+            }
+            Type::Map => {
+                self.enter_container(slot, ancestors)?;
+                let keys = self.scratch_slots(3);
+                self.map_keys(slot, keys)?;
+                let count = self.get_list_count(keys);
+                let mut map = Vec::with_capacity(count as usize);
+                for index in 0..count {
+                    self.list_get(keys, index, keys + 1);
+                    self.get_map_value(slot, keys + 1, keys + 2);
+                    let key = self.copy_value(keys + 1, ancestors)?;
+                    let value = self.copy_value(keys + 2, ancestors)?;
+                    map.push((key, value));
+                }
+                ancestors.pop();
+                Value::Map(map)
+            }
+        };
+        Ok(value)
+    }
+    fn enter_container(&mut self, slot: i32, ancestors: &mut Vec<u64>) -> Result<(), Error> {
+        let identity = self.get_slot_handle(slot).identity();
+        if ancestors.contains(&identity) {
+            return Err(Error::Cycle);
+        }
+        ancestors.push(identity);
+        Ok(())
+    }
+    /// Fails without touching the slot when a list, map or foreign object is
+    /// used as a map key anywhere in `value`, wren can only hash value types,
+    /// and classes and ranges which are opaque here.
+    pub fn set_value(&mut self, slot: i32, value: &Value) -> Result<(), Error> {
+        value.check_keys()?;
+        self.restoring_slots(|vm| vm.write_value(slot, value));
+        Ok(())
+    }
+    fn write_value(&mut self, slot: i32, value: &Value) {
+        match value {
+            Value::Null => self.set_slot_null(slot),
+            Value::Bool(value) => self.set_slot_bool(slot, *value),
+            Value::Num(value) => self.set_slot_double(slot, *value),
+            Value::String(bytes) => self.set_slot_bytes(slot, bytes),
+            Value::Foreign(handle) | Value::Object(handle) => self.set_slot_handle(slot, handle),
+            Value::List(list) => {
+                let element = self.scratch_slots(1);
+                self.set_slot_new_list(slot);
+                for value in list {
+                    self.write_value(element, value);
+                    self.list_push(slot, element);
+                }
+            }
+            Value::Map(map) => {
+                let key = self.scratch_slots(2);
+                self.set_slot_new_map(slot);
+                for (k, v) in map {
+                    self.write_value(key, k);
+                    self.write_value(key + 1, v);
+                    self.set_map_value(slot, key, key + 1);
+                }
+            }
+        }
+    }
+}
+
+impl Value {
+    fn check_keys(&self) -> Result<(), Error> {
+        match self {
+            Value::List(list) => list.iter().try_for_each(Value::check_keys),
+            Value::Map(map) => map.iter().try_for_each(|(k, v)| {
+                let found = match k {
+                    Value::List(_) => Type::List,
+                    Value::Map(_) => Type::Map,
+                    Value::Foreign(_) => Type::Foreign,
+                    _ => return v.check_keys(),
+                };
+                Err(Error::MapKey { found })
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+// Handles are equal when they refer to the same object, and maps when they
+// have the same entries in any order.
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Null, Value::Null) => true,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Num(a), Value::Num(b)) => a == b,
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::List(a), Value::List(b)) => a == b,
+            (Value::Map(a), Value::Map(b)) => {
+                a.len() == b.len() && a.iter().all(|entry| b.contains(entry))
+            }
+            (Value::Foreign(a), Value::Foreign(b)) | (Value::Object(a), Value::Object(b)) => {
+                a.identity() == b.identity()
+            }
+            _ => false,
+        }
+    }
+}
+
+// Formats values much like wren's toString.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "null"),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Num(value) if value.is_nan() => write!(f, "nan"),
+            Value::Num(value) if value.is_infinite() => {
+                write!(f, "{}infinity", if *value < 0.0 { "-" } else { "" })
+            }
+            Value::Num(value) => write!(f, "{}", value),
+            Value::String(bytes) => write!(f, "{}", String::from_utf8_lossy(bytes)),
+            Value::List(list) => {
+                write!(f, "[")?;
+                for (i, value) in list.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            Value::Map(map) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in map.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", key, value)?;
+                }
+                write!(f, "}}")
+            }
+            Value::Foreign(_) => write!(f, "instance of foreign class"),
+            Value::Object(_) => write!(f, "instance"),
+        }
+    }
+}
+
+impl<'a> FromWren<'a> for Value {
+    fn from_wren(vm: &'a mut VM, slot: i32) -> Result<Self, Error> {
+        vm.get_value(slot)
+    }
+}
+
+// Panics on invalid map keys since ToWren can't fail, use `VM::set_value` to
+// get an error instead.
+impl ToWren for Value {
+    fn to_wren(&self, vm: &mut VM, slot: i32) {
+        if let Err(err) = vm.set_value(slot, self) {
+            panic!("{}", err);
+        }
+    }
+}
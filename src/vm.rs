@@ -1,124 +1,287 @@
 use libc::{c_char, c_int};
-use std::ffi::{CStr, CString};
-use std::{fs, mem, path, slice};
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::ffi::CString;
+use std::io::{LineWriter, Write};
+use std::ops::Range;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use std::{fs, mem, ptr, slice};
 
+use crate::convert::ToWren;
+use crate::error::ErrorBuffer;
 use crate::ffi;
-use crate::{InterpretResult, Point, Type};
+use crate::foreign::{self, ClassMethods, ForeignClass};
+use crate::helper::Helper;
+use crate::interrupt::{self, Budget};
+use crate::loader::{self, FileLoader, ModuleLoader};
+use crate::macros;
+use crate::memory::{self, GcStats, Tracker};
+use crate::output::{self, Output};
+use crate::path::{self, PathType};
+use crate::scheduler::{Clock, Timers};
+#[cfg(feature = "async")]
+use crate::task::Task;
+use crate::{Error, ForeignClassMethods, ForeignMethodFn, InterpretResult, Point, Type};
+
+pub type ForeignMethod = Box<dyn FnMut(&mut VM)>;
 
 fn default_write(_: &mut VM, text: &str) {
     print!("{}", text);
 }
 
-fn default_error(_: &mut VM, _type: ffi::WrenErrorType, module: &str, line: i32, message: &str) {
-    match _type {
-        ffi::WrenErrorType::Compile => println!("[{} line {}] {}", module, line, message),
-        ffi::WrenErrorType::Runtime => println!("{}", message),
-        ffi::WrenErrorType::StackTrace => println!("[{} line {}] in {}", module, line, message),
+pub struct ForeignData<T> {
+    inner: *mut T,
+}
+
+impl<T> ForeignData<T> {
+    pub(crate) fn new(inner: *mut T) -> ForeignData<T> {
+        ForeignData { inner }
+    }
+    pub fn inner(&mut self) -> &mut T {
+        // unsafe { mem::transmute::<*mut T, &mut T>(self.inner) }
+        unsafe { &mut *(self.inner) }
     }
 }
 
-fn default_load_module(_: &mut VM, module: &str) -> Vec<u8> {
-    let mut path = path::PathBuf::from(module);
-    path.set_extension("wren");
-    fs::read(path).unwrap()
+// Per-VM state kept behind wren's user data pointer, so every trampoline
+// that only receives a `*mut WrenVM` can find its way back to it.
+pub(crate) struct VmData {
+    core: Rc<Core>,
+    pub(crate) user_data: Point,
+    methods: Vec<Dispatch>,
+    registry: HashMap<MethodKey, Registered>,
+    bind_method_fn: ffi::WrenBindForeignMethodFn,
+    classes: HashMap<(String, String), ForeignClassMethods>,
+    bind_class_fn: ffi::WrenBindForeignClassFn,
+    pub(crate) helper: Option<Helper>,
+    pub(crate) call_handles: HashMap<String, Handle>,
+    foreign_depth: usize,
+    errors: ErrorBuffer,
+    error_fn: ffi::WrenErrorFn,
+    pub(crate) memory: Tracker,
+    pub(crate) budget: Budget,
+    pub(crate) interrupt_message: Option<CString>,
+    pub(crate) loader: Option<Box<dyn ModuleLoader>>,
+    pub(crate) output: Option<Output>,
+    pub(crate) scheduler: bool,
+    pub(crate) timers: Timers,
+    #[cfg(feature = "async")]
+    pub(crate) tasks: Vec<Task>,
 }
 
-fn get_file_name(path: &str) -> &str {
-    let v: Vec<&str> = path.rsplit('/').collect();
-    let name = v[0];
-    let file = name.split_once('.').unwrap();
-    file.0
+impl VmData {
+    fn new() -> VmData {
+        VmData {
+            core: Rc::new(Core {
+                raw: Cell::new(ptr::null_mut()),
+                live: RefCell::new(HashSet::new()),
+            }),
+            user_data: ptr::null_mut(),
+            methods: Vec::new(),
+            registry: HashMap::new(),
+            bind_method_fn: None,
+            classes: HashMap::new(),
+            bind_class_fn: None,
+            helper: None,
+            call_handles: HashMap::new(),
+            foreign_depth: 0,
+            errors: ErrorBuffer::default(),
+            error_fn: None,
+            memory: Tracker::default(),
+            budget: Budget::default(),
+            interrupt_message: None,
+            loader: None,
+            output: None,
+            scheduler: false,
+            timers: Timers::new(),
+            #[cfg(feature = "async")]
+            tasks: Vec::new(),
+        }
+    }
+    // Null until wrenNewVM() has returned.
+    pub(crate) fn raw(&self) -> *mut ffi::WrenVM {
+        self.core.raw.get()
+    }
 }
 
-enum PathType {
-    Absolute,
-    Relative,
-    Simple,
+#[derive(PartialEq, Eq, Hash)]
+struct MethodKey {
+    module: String,
+    class_name: String,
+    is_static: bool,
+    signature: String,
 }
 
-impl PartialEq for PathType {
-    #[inline]
-    fn eq(&self, other: &PathType) -> bool {
-        if mem::discriminant(self) == mem::discriminant(other) {
-            matches!(
-                (self, other),
-                (PathType::Absolute, PathType::Absolute)
-                    | (PathType::Relative, PathType::Relative)
-                    | (PathType::Simple, PathType::Simple)
-            )
-        } else {
-            false
+impl MethodKey {
+    fn new(module: &str, class_name: &str, is_static: bool, signature: &str) -> MethodKey {
+        MethodKey {
+            module: module.to_string(),
+            class_name: class_name.to_string(),
+            is_static,
+            signature: signature.to_string(),
         }
     }
 }
 
-#[inline]
-fn is_separator(c: char) -> bool {
-    if c == '/' {
-        return true;
+impl std::fmt::Display for MethodKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_static {
+            write!(f, "static ")?;
+        }
+        write!(f, "{}.{}", self.class_name, self.signature)
     }
+}
 
-    if cfg!(target_os = "windows") && c == '\\' {
-        return true;
-    }
-    false
+// A slot of the dispatch table. The method is taken out while it runs.
+enum Dispatch {
+    Free,
+    Running,
+    Method(ForeignMethod),
 }
 
-fn path_type(path: &[u8]) -> PathType {
-    let first = *path.first().unwrap();
-    let first = char::from(first);
-    let second = *path.get(1).unwrap();
-    let second = char::from(second);
-    let third = *path.get(2).unwrap();
-    let third = char::from(third);
-    if cfg!(target_os = "windows") && first.is_ascii_alphabetic() && second == ':' {
-        return PathType::Absolute;
-    }
+// Registered closures only take up a dispatch slot once wren actually binds
+// them.
+enum Registered {
+    Unbound(ForeignMethod),
+    Bound(ForeignMethodFn),
+}
 
-    if is_separator(first) {
-        return PathType::Absolute;
+fn bind_registered_method(
+    vm: &mut VM,
+    module: &str,
+    class_name: &str,
+    is_static: bool,
+    signature: &str,
+) -> ForeignMethodFn {
+    let key = MethodKey::new(module, class_name, is_static, signature);
+    match vm.data().registry.remove(&key) {
+        Some(Registered::Unbound(method)) => match vm.push_method(method) {
+            Ok(f) => {
+                vm.data().registry.insert(key, Registered::Bound(f));
+                return f;
+            }
+            // Left unbound, wren reports the method as missing.
+            Err((method, _)) => {
+                vm.data().registry.insert(key, Registered::Unbound(method));
+                return None;
+            }
+        },
+        Some(Registered::Bound(f)) => {
+            vm.data().registry.insert(key, Registered::Bound(f));
+            return f;
+        }
+        None => {}
     }
 
-    if first == '.' && is_separator(second) || first == '.' && second == '.' && is_separator(third)
-    {
-        return PathType::Relative;
+    if let Some(bind) = vm.data().bind_method_fn {
+        let module = CString::new(module).unwrap();
+        let class = CString::new(class_name).unwrap();
+        let signature = CString::new(signature).unwrap();
+        let f = unsafe {
+            bind(
+                vm.raw,
+                module.as_ptr(),
+                class.as_ptr(),
+                is_static as c_int,
+                signature.as_ptr(),
+            )
+        };
+        if f.is_some() {
+            return f;
+        }
     }
 
-    PathType::Simple
+    let mut candidates: Vec<String> = vm
+        .data()
+        .registry
+        .keys()
+        .filter(|k| k.module == key.module && k.class_name == key.class_name)
+        .map(|k| k.to_string())
+        .collect();
+    candidates.sort();
+    // wren follows up with a runtime error of its own, this ends up under it.
+    let mut note = format!(
+        "No foreign method registered for `{}` in module \"{}\"",
+        key, module
+    );
+    if !candidates.is_empty() {
+        note.push_str(&format!(" (registered: {})", candidates.join(", ")));
+    }
+    vm.data().errors.note(note);
+    None
 }
 
-fn default_resolve_module(_: &mut VM, module: &str, importer: &str) -> Vec<u8> {
-    if path_type(importer.as_bytes()) == PathType::Simple {
-        return Vec::from(importer);
+fn buffer_error(vm: &mut VM, _type: ffi::WrenErrorType, module: &str, line: i32, message: &str) {
+    vm.data().errors.push(_type, module, line, message);
+    if let Some(error_fn) = vm.data().error_fn {
+        let module = CString::new(module).unwrap();
+        let message = CString::new(message).unwrap();
+        unsafe { error_fn(vm.raw, _type, module.as_ptr(), line, message.as_ptr()) }
     }
-    let mut path = path::PathBuf::from(module);
-    path.pop();
-
-    path.push(importer);
-    let p = path.to_str().unwrap_or("");
-    Vec::from(p)
 }
 
-pub struct ForeignData<T> {
-    inner: *mut T,
-}
+fn bind_registered_class(vm: &mut VM, module: &str, class_name: &str) -> ForeignClassMethods {
+    let key = (module.to_string(), class_name.to_string());
+    if let Some(methods) = vm.data().classes.get(&key) {
+        return *methods;
+    }
 
-impl<T> ForeignData<T> {
-    pub fn inner(&mut self) -> &mut T {
-        // unsafe { mem::transmute::<*mut T, &mut T>(self.inner) }
-        unsafe { &mut *(self.inner) }
+    match vm.data().bind_class_fn {
+        Some(bind) => {
+            let module = CString::new(module).unwrap();
+            let class = CString::new(class_name).unwrap();
+            unsafe { bind(vm.raw, module.as_ptr(), class.as_ptr()) }
+        }
+        None => ForeignClassMethods {
+            allocate: None,
+            finalize: None,
+        },
     }
 }
 
 // fn resolve_module(_: &mut VM, module: &str, importer: &str) -> String {}
 pub struct VM {
-    raw: *mut ffi::WrenVM,
+    pub(crate) raw: *mut ffi::WrenVM,
     owned: bool,
 }
 impl VM {
-    pub fn new(config: &mut Configuration) -> VM {
-        let raw = unsafe { ffi::wrenNewVM(&mut config.0) };
-        VM { raw, owned: true }
+    /// The configuration is used up, closures and loaders can only belong to
+    /// one VM.
+    pub fn new(mut config: Configuration) -> VM {
+        let mut data = VmData::new();
+        data.bind_method_fn = config.raw.bind_foreign_method_fn;
+        data.registry = config
+            .methods
+            .into_iter()
+            .map(|(key, method)| (key, Registered::Unbound(method)))
+            .collect();
+        data.bind_class_fn = config.raw.bind_foreign_class_fn;
+        data.classes = config.classes;
+        data.error_fn = config.raw.error_fn;
+        data.memory.reallocate_fn = config.raw.reallocate_fn;
+        data.memory.limit.set(config.memory_limit);
+        data.budget.time_limit = config.time_limit;
+        data.budget.step_limit = config.step_limit;
+        data.loader = config.loader;
+        data.output = config.output;
+        data.scheduler = config.scheduler;
+        if config.virtual_clock {
+            data.timers.clock = Clock::Virtual(Duration::ZERO);
+        }
+        let data = Box::into_raw(Box::new(data));
+        config.raw.user_data = data as Point;
+        config.raw.bind_foreign_method_fn = wren_bind_foreign_method_fn!(bind_registered_method);
+        config.raw.bind_foreign_class_fn = wren_bind_foreign_class_fn!(bind_registered_class);
+        config.raw.error_fn = wren_error_fn!(buffer_error);
+        config.raw.reallocate_fn = Some(memory::reallocate);
+        let raw = unsafe { ffi::wrenNewVM(&mut config.raw) };
+        unsafe { ffi::wrenSetInterruptFn(raw, Some(interrupt::interrupt)) };
+        let mut vm = VM { raw, owned: true };
+        vm.data().core.raw.set(raw);
+        let helper = Helper::new(&mut vm);
+        vm.data().helper = Some(helper);
+        vm
     }
     pub fn from_ptr(ptr: *mut ffi::WrenVM) -> VM {
         VM {
@@ -126,24 +289,31 @@ impl VM {
             owned: false,
         }
     }
-    pub fn read_file(&mut self, path: &str) -> InterpretResult {
-        let path_bytes = path.as_bytes();
-        let file = get_file_name(path);
-        let source = fs::read(path).unwrap();
-        let mut path = path::PathBuf::from(path);
-        let module = if path_type(path_bytes) == PathType::Simple {
-            path.as_path().file_stem().unwrap()
-        } else {
-            path.pop();
-            path.push(file);
-            path.as_os_str()
-        };
-        self.interpret(module.to_str().unwrap_or("default"), source)
+    /// The module is named after the file, so relative imports are taken from
+    /// its directory. "src/main.wren" becomes "./src/main" rather than a
+    /// logical name.
+    pub fn read_file(&mut self, path: &str) -> Result<(), Error> {
+        let source = fs::read(path)?;
+        let module = path::remove_extension(path);
+        match path::path_type(module) {
+            PathType::Simple => self.interpret(&format!("./{}", module), source),
+            _ => self.interpret(module, source),
+        }
     }
-    pub fn interpret<S: Into<Vec<u8>>>(&mut self, module: &str, source: S) -> InterpretResult {
-        let module = CString::new(module).unwrap();
-        let source = CString::new(source.into()).unwrap();
-        unsafe { ffi::wrenInterpret(self.raw, module.as_ptr(), source.as_ptr()) }
+    pub fn interpret<S: Into<Vec<u8>>>(&mut self, module: &str, source: S) -> Result<(), Error> {
+        let module = CString::new(module)?;
+        let source = CString::new(source.into())?;
+        self.data().errors = ErrorBuffer::default();
+        self.data().budget.start();
+        let result = unsafe { ffi::wrenInterpret(self.raw, module.as_ptr(), source.as_ptr()) };
+        self.take_result(result)
+    }
+    fn take_result(&mut self, result: InterpretResult) -> Result<(), Error> {
+        let errors = mem::take(&mut self.data().errors);
+        match result {
+            InterpretResult::Success => Ok(()),
+            _ => Err(errors.into_error(result)),
+        }
     }
     // pub fn close(&mut self) {
     //     unsafe { ffi::wrenFreeVM(self.raw) }
@@ -152,16 +322,20 @@ impl VM {
         let v = unsafe { ffi::wrenGetVersionNumber() };
         println!("Wren Version: {}", v);
     }
-    pub fn collect_garbage(&mut self) {
+    pub fn collect_garbage(&mut self) -> GcStats {
+        let before = self.data().memory.stats().current;
+        let start = Instant::now();
         unsafe { ffi::wrenCollectGarbage(self.raw) }
+        GcStats {
+            before,
+            after: self.data().memory.stats().current,
+            duration: start.elapsed(),
+        }
     }
-    pub fn make_call_handle(&mut self, signature: &str) -> Handle {
-        let signature = CString::new(signature).unwrap();
+    pub fn make_call_handle(&mut self, signature: &str) -> Result<Handle, Error> {
+        let signature = CString::new(signature)?;
         let handle = unsafe { ffi::wrenMakeCallHandle(self.raw, signature.as_ptr()) };
-        Handle {
-            raw: handle,
-            vm: self.raw,
-        }
+        Ok(self.wrap_handle(handle))
     }
     pub fn get_slot_count(&mut self) -> i32 {
         unsafe { ffi::wrenGetSlotCount(self.raw) }
@@ -171,15 +345,19 @@ impl VM {
             ffi::wrenEnsureSlots(self.raw, num_slots);
         }
     }
-    pub fn call(&mut self, handle: &Handle) -> InterpretResult {
-        unsafe { ffi::wrenCall(self.raw, handle.raw) }
+    pub fn call(&mut self, handle: &Handle) -> Result<(), Error> {
+        self.check_handle(handle)?;
+        self.data().errors = ErrorBuffer::default();
+        self.data().budget.start();
+        let result = unsafe { ffi::wrenCall(self.raw, handle.raw) };
+        self.take_result(result)
     }
     // pub fn handle_close(&mut self, handle: Handle) {
     //     unsafe { ffi::wrenReleaseHandle(self.0, handle.raw) }
     // }
     pub fn get_slot_type(&mut self, slot: i32) -> Type {
         assert!(
-            self.get_slot_count() > slot,
+            slot >= 0 && self.get_slot_count() > slot,
             "Slot {} is out of bounds",
             slot
         );
@@ -194,8 +372,7 @@ impl VM {
     }
     pub fn get_slot_bytes(&mut self, slot: i32) -> Option<&[u8]> {
         if self.get_slot_type(slot) == Type::String {
-            let len: mem::MaybeUninit<i32> = mem::MaybeUninit::uninit();
-            let mut len = unsafe { len.assume_init() };
+            let mut len = 0;
             let ptr = unsafe { ffi::wrenGetSlotBytes(self.raw, slot, &mut len) };
             Some(unsafe { slice::from_raw_parts(ptr as *const u8, len as usize) })
         } else {
@@ -228,21 +405,15 @@ impl VM {
         let value: *mut T = self.get_slot_foreign(slot);
         ForeignData { inner: value }
     }
-    pub fn get_slot_str(&mut self, slot: i32) -> Option<&str> {
-        if self.get_slot_type(slot) == Type::String {
-            let ptr = unsafe { ffi::wrenGetSlotString(self.raw, slot) };
-            Some(unsafe { CStr::from_ptr(ptr).to_str().unwrap() })
-        } else {
-            None
-        }
+    /// wren strings are byte strings and may hold NULs or invalid UTF-8, so
+    /// get_slot_str fails on what isn't UTF-8. get_slot_string copies the
+    /// exact bytes.
+    pub fn get_slot_str(&mut self, slot: i32) -> Result<&str, Error> {
+        self.get(slot)
     }
-    pub fn get_slot_string(&mut self, slot: i32) -> Option<String> {
-        if self.get_slot_type(slot) == Type::String {
-            let ptr = unsafe { ffi::wrenGetSlotString(self.raw, slot) };
-            Some(unsafe { CString::from_raw(ptr as *mut i8).into_string().unwrap() })
-        } else {
-            None
-        }
+    pub fn get_slot_string(&mut self, slot: i32) -> Result<Vec<u8>, Error> {
+        self.expect_type(slot, Type::String)?;
+        Ok(self.get_slot_bytes(slot).unwrap().to_vec())
     }
     pub fn get_slot_handle(&mut self, slot: i32) -> Handle {
         assert!(
@@ -250,10 +421,8 @@ impl VM {
             "Slot {} is out of bounds",
             slot
         );
-        Handle {
-            raw: unsafe { ffi::wrenGetSlotHandle(self.raw, slot) },
-            vm: self.raw,
-        }
+        let handle = unsafe { ffi::wrenGetSlotHandle(self.raw, slot) };
+        self.wrap_handle(handle)
     }
     pub fn set_slot_bool(&mut self, slot: i32, value: bool) {
         unsafe { ffi::wrenSetSlotBool(self.raw, slot, value as c_int) }
@@ -288,10 +457,14 @@ impl VM {
         unsafe { ffi::wrenSetSlotNull(self.raw, slot) }
     }
     pub fn set_slot_string(&mut self, slot: i32, s: &str) {
-        let cstr = CString::new(s).unwrap();
-        unsafe { ffi::wrenSetSlotString(self.raw, slot, cstr.as_ptr()) }
+        self.set_slot_bytes(slot, s.as_bytes())
     }
+    /// Panics when the handle belongs to another VM or its VM is gone, since
+    /// ToWren can't fail. `call` and `call_method` report that as an error.
     pub fn set_slot_handle(&mut self, slot: i32, handle: &Handle) {
+        if let Err(err) = self.check_handle(handle) {
+            panic!("{}", err);
+        }
         unsafe { ffi::wrenSetSlotHandle(self.raw, slot, handle.raw) }
     }
     pub fn get_list_count(&mut self, slot: i32) -> i32 {
@@ -301,37 +474,112 @@ impl VM {
             0
         }
     }
-    fn check_index(&mut self, list_slot: i32, index: i32) -> i32 {
-        assert!(
-            self.get_slot_type(list_slot) == Type::List,
-            "Slot {} must contain a list",
-            list_slot
-        );
-        let list_count = self.get_list_count(list_slot);
-        let index = if index < 0 {
-            list_count + 1 + index
-        } else {
-            index
-        };
-        assert!(index <= list_count, "List index out of bounds");
-        index
-    }
-    pub fn get_list_element(&mut self, list_slot: i32, index: i32, element_slot: i32) {
-        let index = self.check_index(list_slot, index);
-        unsafe { ffi::wrenGetListElement(self.raw, list_slot, index, element_slot) }
+    // Negative indices count from the end like they do in wren. Inserting
+    // allows one past the end, where -1 appends.
+    fn check_index(&mut self, list_slot: i32, index: i32, insert: bool) -> Result<i32, Error> {
+        self.check_slot(list_slot)?;
+        let found = self.get_slot_type(list_slot);
+        if found != Type::List {
+            return Err(Error::Type {
+                expected: Type::List,
+                found,
+            });
+        }
+        let count = self.get_list_count(list_slot);
+        let end = if insert { count + 1 } else { count };
+        let checked = if index < 0 { end + index } else { index };
+        if checked < 0 || checked >= end {
+            return Err(Error::Index { index, count });
+        }
+        Ok(checked)
     }
-    pub fn set_list_element(&mut self, list_slot: i32, index: i32, element_slot: i32) {
-        let index = self.check_index(list_slot, index);
+    pub(crate) fn check_slot(&mut self, slot: i32) -> Result<(), Error> {
+        let count = self.get_slot_count();
+        if slot < 0 || slot >= count {
+            return Err(Error::Slot { slot, count });
+        }
+        Ok(())
+    }
+    pub fn get_list_element(
+        &mut self,
+        list_slot: i32,
+        index: i32,
+        element_slot: i32,
+    ) -> Result<(), Error> {
+        let index = self.check_index(list_slot, index, false)?;
+        self.check_slot(element_slot)?;
+        self.list_get(list_slot, index, element_slot);
+        Ok(())
+    }
+    pub fn set_list_element(
+        &mut self,
+        list_slot: i32,
+        index: i32,
+        element_slot: i32,
+    ) -> Result<(), Error> {
+        let index = self.check_index(list_slot, index, false)?;
+        self.check_slot(element_slot)?;
         unsafe { ffi::wrenSetListElement(self.raw, list_slot, index, element_slot) }
-    }
-    pub fn insert_in_list(&mut self, list_slot: i32, index: i32, element_slot: i32) {
-        assert!(
-            element_slot < self.get_slot_count(),
-            "No element in slot {}",
-            element_slot
-        );
-        let index = self.check_index(list_slot, index);
+        Ok(())
+    }
+    pub fn insert_in_list(
+        &mut self,
+        list_slot: i32,
+        index: i32,
+        element_slot: i32,
+    ) -> Result<(), Error> {
+        let index = self.check_index(list_slot, index, true)?;
+        self.check_slot(element_slot)?;
         unsafe { ffi::wrenInsertInList(self.raw, list_slot, index, element_slot) }
+        Ok(())
+    }
+    /// Stores the removed element into `removed_slot`.
+    pub fn remove_from_list(
+        &mut self,
+        list_slot: i32,
+        index: i32,
+        removed_slot: i32,
+    ) -> Result<(), Error> {
+        let index = self.check_index(list_slot, index, false)?;
+        self.check_slot(removed_slot)?;
+        self.list_remove(list_slot, index, removed_slot)
+    }
+    pub fn clear_list(&mut self, list_slot: i32) -> Result<(), Error> {
+        self.check_index(list_slot, 0, true)?;
+        self.list_clear(list_slot)
+    }
+    /// Copies the elements of the list into fresh slots past the ones in use
+    /// and returns them.
+    pub fn list_iter(&mut self, list_slot: i32) -> Result<Range<i32>, Error> {
+        self.check_index(list_slot, 0, true)?;
+        let count = self.get_list_count(list_slot);
+        let first = self.scratch_slots(count);
+        for index in 0..count {
+            self.list_get(list_slot, index, first + index);
+        }
+        Ok(first..first + count)
+    }
+    pub fn set_slot_list_from_iter<T, I>(&mut self, slot: i32, iter: I)
+    where
+        T: ToWren,
+        I: IntoIterator<Item = T>,
+    {
+        self.restoring_slots(|vm| {
+            let element = vm.scratch_slots(1);
+            vm.set_slot_new_list(slot);
+            for value in iter {
+                value.to_wren(vm, element);
+                vm.list_push(slot, element);
+            }
+        })
+    }
+    // Unchecked versions for when the crate already knows the list and index
+    // are fine.
+    pub(crate) fn list_get(&mut self, list_slot: i32, index: i32, element_slot: i32) {
+        unsafe { ffi::wrenGetListElement(self.raw, list_slot, index, element_slot) }
+    }
+    pub(crate) fn list_push(&mut self, list_slot: i32, element_slot: i32) {
+        unsafe { ffi::wrenInsertInList(self.raw, list_slot, -1, element_slot) }
     }
     pub fn get_map_count(&mut self, slot: i32) -> i32 {
         if self.get_slot_type(slot) == Type::Map {
@@ -366,90 +614,382 @@ impl VM {
         );
         unsafe { ffi::wrenRemoveMapValue(self.raw, map_slot, key_slot, removed_value_slot) }
     }
-    pub fn get_variable(&mut self, module: &str, name: &str, slot: i32) {
-        let module_cstr = CString::new(module).unwrap();
-        let name_cstr = CString::new(name).unwrap();
+    pub fn get_variable(&mut self, module: &str, name: &str, slot: i32) -> Result<(), Error> {
+        let module_cstr = CString::new(module)?;
+        let name_cstr = CString::new(name)?;
+        // wren only asserts that both exist.
+        if !self.has_variablle(module, name) {
+            return Err(Error::Variable {
+                module: module.to_string(),
+                name: name.to_string(),
+            });
+        }
+        if slot < 0 {
+            return Err(Error::Slot {
+                slot,
+                count: self.get_slot_count(),
+            });
+        }
+        self.ensure_slots(slot + 1);
         unsafe { ffi::wrenGetVariable(self.raw, module_cstr.as_ptr(), name_cstr.as_ptr(), slot) }
+        Ok(())
     }
+    /// Names with NULs in them can't exist in wren to begin with.
     pub fn has_variablle(&mut self, module: &str, name: &str) -> bool {
-        let module_cstr = CString::new(module).unwrap();
-        let name_cstr = CString::new(name).unwrap();
-        unsafe { ffi::wrenHasVariable(self.raw, module_cstr.as_ptr(), name_cstr.as_ptr()) != 0 }
+        if !self.has_module(module) {
+            return false;
+        }
+        match (CString::new(module), CString::new(name)) {
+            (Ok(module), Ok(name)) => unsafe {
+                ffi::wrenHasVariable(self.raw, module.as_ptr(), name.as_ptr()) != 0
+            },
+            _ => false,
+        }
     }
     pub fn has_module(&mut self, module: &str) -> bool {
-        let module_cstr = CString::new(module).unwrap();
-        unsafe { ffi::wrenHasModule(self.raw, module_cstr.as_ptr()) != 0 }
+        match CString::new(module) {
+            Ok(module) => unsafe { ffi::wrenHasModule(self.raw, module.as_ptr()) != 0 },
+            Err(_) => false,
+        }
     }
     pub fn abort_fiber(&mut self, slot: i32) {
         unsafe { ffi::wrenAbortFiber(self.raw, slot) }
     }
     pub fn get_user_data<T>(&mut self) -> &mut T {
-        unsafe { &mut *(ffi::wrenGetUserData(self.raw) as *mut T) }
+        unsafe { &mut *(self.data().user_data as *mut T) }
     }
     unsafe fn _set_user_data<T>(&mut self, user_data: *mut T) {
         let user_data = mem::transmute::<*mut T, Point>(user_data);
-        ffi::wrenSetUserData(self.raw, user_data)
+        self.data().user_data = user_data;
     }
     pub fn set_user_data<T>(&mut self, user_data: &mut T) {
         unsafe {
             self._set_user_data::<T>(user_data as *mut T);
         }
     }
+    /// Every closure takes up one of the MAX_FOREIGN_METHODS dispatch slots of
+    /// the VM until it is removed, including the ones bound from the registry.
+    /// The function only works with this VM.
+    pub fn foreign_method<F: FnMut(&mut VM) + 'static>(
+        &mut self,
+        f: F,
+    ) -> Result<ForeignMethodFn, Error> {
+        self.push_method(Box::new(f)).map_err(|(_, err)| err)
+    }
+    fn push_method(
+        &mut self,
+        method: ForeignMethod,
+    ) -> Result<ForeignMethodFn, (ForeignMethod, Error)> {
+        let methods = &mut self.data().methods;
+        let index = match methods
+            .iter()
+            .position(|slot| matches!(slot, Dispatch::Free))
+        {
+            Some(index) => index,
+            None if methods.len() < macros::MAX_FOREIGN_METHODS => {
+                methods.push(Dispatch::Free);
+                methods.len() - 1
+            }
+            None => {
+                let err = Error::MethodLimit {
+                    limit: macros::MAX_FOREIGN_METHODS,
+                };
+                return Err((method, err));
+            }
+        };
+        methods[index] = Dispatch::Method(method);
+        Ok(macros::_closure_fn(index))
+    }
+    /// Frees the dispatch slot of a closure from `foreign_method` or the
+    /// registry, for when wren no longer calls it, e.g. after binding the
+    /// method again. Calling it anyway aborts the fiber. False when `f` isn't
+    /// a closure of this VM.
+    pub fn remove_foreign_method(&mut self, f: ForeignMethodFn) -> bool {
+        let Some(address) = f.map(|f| f as usize) else {
+            return false;
+        };
+        let count = self.data().methods.len();
+        let Some(index) = (0..count)
+            .find(|&index| macros::_closure_fn(index).map(|f| f as usize) == Some(address))
+        else {
+            return false;
+        };
+        let data = self.data();
+        if matches!(data.methods[index], Dispatch::Free) {
+            return false;
+        }
+        data.methods[index] = Dispatch::Free;
+        data.registry.retain(|_, registered| match registered {
+            Registered::Bound(bound) => bound.map(|f| f as usize) != Some(address),
+            Registered::Unbound(_) => true,
+        });
+        true
+    }
+    pub(crate) fn data(&mut self) -> &mut VmData {
+        unsafe { &mut *(ffi::wrenGetUserData(self.raw) as *mut VmData) }
+    }
+    // The message is what the calling fiber gets aborted with when there is
+    // no method to run.
+    pub(crate) fn take_method(&mut self, index: usize) -> Result<ForeignMethod, &'static str> {
+        let slot = match self.data().methods.get_mut(index) {
+            Some(slot) => slot,
+            None => return Err("Foreign method is not bound to this VM"),
+        };
+        match mem::replace(slot, Dispatch::Running) {
+            Dispatch::Method(method) => Ok(method),
+            Dispatch::Running => Err("Foreign method is already running"),
+            Dispatch::Free => {
+                *slot = Dispatch::Free;
+                Err("Foreign method is not bound to this VM")
+            }
+        }
+    }
+    // A method that removed itself while running stays removed.
+    pub(crate) fn restore_method(&mut self, index: usize, method: ForeignMethod) {
+        let slot = &mut self.data().methods[index];
+        if matches!(slot, Dispatch::Running) {
+            *slot = Dispatch::Method(method);
+        }
+    }
+    // Copies `bytes` into a NUL-terminated buffer that wren is allowed to free.
+    // Returns null if `bytes` contains a NUL itself.
+    pub(crate) fn alloc_c_string(&mut self, bytes: &[u8]) -> *const c_char {
+        if bytes.contains(&0) {
+            return ptr::null();
+        }
+        let size = bytes.len() + 1;
+        unsafe {
+            let ptr = memory::reallocate(ptr::null_mut(), size, ffi::wrenGetUserData(self.raw))
+                as *mut u8;
+            if ptr.is_null() {
+                return ptr::null();
+            }
+            ptr::copy_nonoverlapping(bytes.as_ptr(), ptr, bytes.len());
+            *ptr.add(bytes.len()) = 0;
+            ptr as *const c_char
+        }
+    }
+    fn wrap_handle(&mut self, raw: *mut ffi::WrenHandle) -> Handle {
+        let core = self.data().core.clone();
+        core.live.borrow_mut().insert(raw);
+        let identity = unsafe { ffi::wrenGetHandleIdentity(raw) };
+        Handle {
+            raw,
+            core,
+            identity,
+        }
+    }
+    pub(crate) fn check_handle(&self, handle: &Handle) -> Result<(), Error> {
+        let owner = handle.core.raw.get();
+        if owner.is_null() {
+            Err(Error::StaleHandle)
+        } else if owner != self.raw {
+            Err(Error::WrongVm)
+        } else {
+            Ok(())
+        }
+    }
+    pub(crate) fn enter_foreign_method(&mut self) {
+        self.data().foreign_depth += 1;
+    }
+    pub(crate) fn leave_foreign_method(&mut self) {
+        self.data().foreign_depth -= 1;
+    }
+    pub(crate) fn in_foreign_method(&mut self) -> bool {
+        self.data().foreign_depth > 0
+    }
 }
 
 impl Drop for VM {
     fn drop(&mut self) {
         if self.owned {
-            unsafe { ffi::wrenFreeVM(self.raw) }
+            unsafe {
+                let data = ffi::wrenGetUserData(self.raw) as *mut VmData;
+                drop((*data).helper.take());
+                (*data).call_handles.clear();
+                // Handles the user still holds are released now, while the VM
+                // is around, and become inert when they are dropped later.
+                let core = &(*data).core;
+                for handle in core.live.borrow_mut().drain() {
+                    ffi::wrenReleaseHandle(self.raw, handle);
+                }
+                core.raw.set(ptr::null_mut());
+                ffi::wrenFreeVM(self.raw);
+                drop(Box::from_raw(data));
+            }
         }
     }
 }
 
+// Shared by a VM and all of its handles, so a handle knows which VM it came
+// from and whether that VM is still alive.
+struct Core {
+    raw: Cell<*mut ffi::WrenVM>,
+    live: RefCell<HashSet<*mut ffi::WrenHandle>>,
+}
+
 pub struct Handle {
     raw: *mut ffi::WrenHandle,
-    vm: *mut ffi::WrenVM,
+    core: Rc<Core>,
+    identity: u64,
+}
+
+impl Handle {
+    // Taken when the handle is made, so it can still be read after the VM is
+    // gone.
+    pub(crate) fn identity(&self) -> u64 {
+        self.identity
+    }
+}
+
+impl std::fmt::Debug for Handle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Handle({:#x})", self.identity())
+    }
 }
 
 impl Drop for Handle {
     fn drop(&mut self) {
-        unsafe { ffi::wrenReleaseHandle(self.vm, self.raw) }
+        let vm = self.core.raw.get();
+        if !vm.is_null() && self.core.live.borrow_mut().remove(&self.raw) {
+            unsafe { ffi::wrenReleaseHandle(vm, self.raw) }
+        }
     }
 }
 
-pub struct Configuration(ffi::WrenConfiguration);
+pub struct Configuration {
+    raw: ffi::WrenConfiguration,
+    methods: HashMap<MethodKey, ForeignMethod>,
+    classes: HashMap<(String, String), ForeignClassMethods>,
+    loader: Option<Box<dyn ModuleLoader>>,
+    output: Option<Output>,
+    memory_limit: Option<usize>,
+    time_limit: Option<Duration>,
+    step_limit: Option<u64>,
+    pub(crate) scheduler: bool,
+    virtual_clock: bool,
+}
 
 impl Configuration {
     pub fn new() -> Configuration {
         let config = std::mem::MaybeUninit::<ffi::WrenConfiguration>::uninit();
         let mut config = unsafe { config.assume_init() };
         unsafe { ffi::wrenInitConfiguration(&mut config) }
-        let mut cfg = Configuration(config);
+        let mut cfg = Configuration {
+            raw: config,
+            methods: HashMap::new(),
+            classes: HashMap::new(),
+            loader: None,
+            output: None,
+            memory_limit: None,
+            time_limit: None,
+            step_limit: None,
+            scheduler: false,
+            virtual_clock: false,
+        };
         cfg.set_write_fn(wren_write_fn!(default_write));
-        cfg.set_error_fn(wren_error_fn!(default_error));
-        cfg.set_load_module_fn(wren_load_module_fn!(default_load_module));
-        cfg.set_resolve_module_fn(wren_resolve_module_fn!(default_resolve_module));
+        cfg.set_module_loader(FileLoader::new());
         cfg
     }
+    /// Heap size in bytes at which the first collection happens.
+    pub fn set_initial_heap_size(&mut self, bytes: usize) {
+        self.raw.initial_heap_size = bytes;
+    }
+    /// The heap is never allowed to shrink below this after a collection.
+    pub fn set_min_heap_size(&mut self, bytes: usize) {
+        self.raw.min_heap_size = bytes;
+    }
+    /// How much the heap may grow past what survived a collection before the
+    /// next one, e.g. 50 to collect again at 150% of the live size.
+    pub fn set_heap_growth_percent(&mut self, percent: u32) {
+        self.raw.heap_growth_percent = percent.min(c_int::MAX as u32) as c_int;
+    }
     pub fn set_reallocate_fn(&mut self, f: ffi::WrenReallocateFn) {
-        self.0.reallocate_fn = f;
+        self.raw.reallocate_fn = f;
+    }
+    /// An allocation that would grow the heap beyond `bytes` fails, and the
+    /// script that made it is aborted with an "Out of memory." runtime error.
+    pub fn set_memory_limit(&mut self, bytes: usize) {
+        self.memory_limit = Some(bytes);
+    }
+    /// Every interpret and call is aborted with a runtime error once it has run
+    /// for longer than `limit`.
+    pub fn set_time_limit(&mut self, limit: Duration) {
+        self.time_limit = Some(limit);
+    }
+    pub fn set_step_limit(&mut self, limit: u64) {
+        self.step_limit = Some(limit);
+    }
+    /// Timer.sleep waits on a clock that only moves with `VM::advance_clock`
+    /// and `VM::run_timers`.
+    pub fn use_virtual_clock(&mut self) {
+        self.virtual_clock = true;
     }
     pub fn set_write_fn(&mut self, f: ffi::WrenWriteFn) {
-        self.0.write_fn = f;
+        self.output = None;
+        self.raw.write_fn = f;
+    }
+    /// Keeps the output in the VM to be picked up with `VM::take_output`.
+    pub fn capture_output(&mut self) {
+        self.set_output(Output::Capture(String::new()));
+    }
+    pub fn set_writer<W: Write + 'static>(&mut self, writer: W) {
+        self.set_output(Output::Writer(Box::new(writer)));
+    }
+    /// Like `set_writer`, but only passes on whole lines.
+    pub fn set_line_writer<W: Write + 'static>(&mut self, writer: W) {
+        let writer: Box<dyn Write> = Box::new(writer);
+        self.set_output(Output::Lines(LineWriter::new(writer)));
+    }
+    fn set_output(&mut self, output: Output) {
+        self.output = Some(output);
+        self.raw.write_fn = wren_write_fn!(output::write_output);
     }
     pub fn set_error_fn(&mut self, f: ffi::WrenErrorFn) {
-        self.0.error_fn = f;
+        self.raw.error_fn = f;
     }
     pub fn set_resolve_module_fn(&mut self, f: ffi::WrenResolveModuleFn) {
-        self.0.resolve_module_fn = f;
+        self.raw.resolve_module_fn = f;
     }
     pub fn set_load_module_fn(&mut self, f: ffi::WrenLoadModuleFn) {
-        self.0.load_module_fn = f;
+        self.raw.load_module_fn = f;
+    }
+    pub fn set_module_loader<L: ModuleLoader + 'static>(&mut self, loader: L) {
+        self.loader = Some(Box::new(loader));
+        self.raw.resolve_module_fn = Some(loader::resolve_module);
+        self.raw.load_module_fn = Some(loader::load_module);
     }
     pub fn set_bind_foreign_method_fn(&mut self, f: ffi::WrenBindForeignMethodFn) {
-        self.0.bind_foreign_method_fn = f;
+        self.raw.bind_foreign_method_fn = f;
     }
     pub fn set_bind_foreign_class_fn(&mut self, f: ffi::WrenBindForeignClassFn) {
-        self.0.bind_foreign_class_fn = f;
+        self.raw.bind_foreign_class_fn = f;
+    }
+    pub fn register_method<F: FnMut(&mut VM) + 'static>(
+        &mut self,
+        module: &str,
+        class_name: &str,
+        is_static: bool,
+        signature: &str,
+        f: F,
+    ) {
+        let key = MethodKey::new(module, class_name, is_static, signature);
+        self.methods.insert(key, Box::new(f));
+    }
+    pub fn register_class<T: ForeignClass>(&mut self, module: &str, class_name: &str) {
+        let methods = ForeignClassMethods {
+            allocate: Some(foreign::allocate::<T>),
+            finalize: Some(foreign::finalize::<T>),
+        };
+        self.classes
+            .insert((module.to_string(), class_name.to_string()), methods);
+
+        let mut methods = ClassMethods::<T>::new();
+        T::methods(&mut methods);
+        for (is_static, signature, method) in methods.into_methods() {
+            let key = MethodKey::new(module, class_name, is_static, &signature);
+            self.methods.insert(key, method);
+        }
     }
 }
 
@@ -0,0 +1,128 @@
+#![cfg(feature = "async")]
+
+use std::future::{self, Future};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+
+use wren_rs::{Configuration, Error, VM};
+
+const IO: &str = "class Io {
+  foreign static wait_(fiber, value)
+  static wait(value) {
+    wait_(Fiber.current, value)
+    return Fiber.suspend()
+  }
+}
+var results = []
+var first = Fn.new { results.add(Io.wait(-1)) }
+var second = Fn.new { results.add(Io.wait(2)) }
+";
+
+// Pending on the first poll, so the VM has to be driven more than once.
+struct YieldOnce(bool);
+
+impl Future for YieldOnce {
+    type Output = ();
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 {
+            return Poll::Ready(());
+        }
+        self.0 = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+// The smallest executor there is: poll, park until woken, repeat.
+fn block_on<F: Future>(future: F) -> F::Output {
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let mut future = Box::pin(future);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+fn io_vm() -> VM {
+    let mut config = Configuration::new();
+    config.register_async_method("main", "Io", true, "wait_(_,_)", |vm| {
+        let value = vm.get_slot_double(2).unwrap_or(0.0);
+        async move {
+            YieldOnce(false).await;
+            if value < 0.0 {
+                Err("negative".to_string())
+            } else {
+                Ok(value)
+            }
+        }
+    });
+    let mut vm = VM::new(config);
+    vm.interpret("main", IO).unwrap();
+    vm
+}
+
+fn start(vm: &mut VM, name: &str) {
+    vm.ensure_slots(1);
+    vm.get_variable("main", name, 0).unwrap();
+    let function = vm.get_slot_handle(0);
+    let fiber = vm.new_fiber(&function).unwrap();
+    fiber.call::<(), _>(vm, ()).unwrap();
+}
+
+fn result_count(vm: &mut VM) -> i32 {
+    vm.ensure_slots(1);
+    vm.get_variable("main", "results", 0).unwrap();
+    vm.get_list_count(0)
+}
+
+#[test]
+fn fibers_resume_under_a_local_executor() {
+    let mut vm = io_vm();
+    start(&mut vm, "second");
+    assert_eq!(vm.pending_tasks(), 1);
+    block_on(future::poll_fn(|cx| vm.poll(cx))).unwrap();
+    assert_eq!(vm.pending_tasks(), 0);
+    assert_eq!(result_count(&mut vm), 1);
+}
+
+#[test]
+fn a_failing_fiber_does_not_drop_the_others() {
+    let mut vm = io_vm();
+    start(&mut vm, "first");
+    start(&mut vm, "second");
+    let result = block_on(future::poll_fn(|cx| vm.poll(cx)));
+    assert!(matches!(result, Err(Error::Runtime { message, .. }) if message == "negative"));
+    assert_eq!(vm.pending_tasks(), 0);
+    assert_eq!(result_count(&mut vm), 1);
+
+    let mut vm = io_vm();
+    start(&mut vm, "first");
+    start(&mut vm, "second");
+    assert!(vm.run_until_idle().is_err());
+    assert_eq!(result_count(&mut vm), 1);
+}
+
+#[test]
+fn the_fiber_must_be_passed_first() {
+    let mut vm = io_vm();
+    for call in ["Io.wait_(1, 2)", "Io.wait_(Fn.new {}, 2)"] {
+        let result = vm.interpret("main", call);
+        assert!(
+            matches!(result, Err(Error::Runtime { message, .. }) if message.contains("Fiber.current"))
+        );
+    }
+    assert_eq!(vm.pending_tasks(), 0);
+}
@@ -0,0 +1,51 @@
+use wren_rs::{Configuration, Error, Frame, VM};
+
+#[test]
+fn compile_errors_have_module_and_line() {
+    let mut vm = VM::new(Configuration::new());
+    match vm.interpret("main", "var a = 1\nvar b = )") {
+        Err(Error::Compile {
+            module,
+            line,
+            message,
+        }) => {
+            assert_eq!(module, "main");
+            assert_eq!(line, 2);
+            assert!(message.contains("Expected expression."), "{}", message);
+        }
+        other => panic!("expected a compile error, got {:?}", other),
+    }
+}
+
+#[test]
+fn later_compile_errors_are_kept() {
+    let mut vm = VM::new(Configuration::new());
+    match vm.interpret("main", "System.print(Foo)\nSystem.print(Bar)") {
+        Err(Error::Compile { line, message, .. }) => {
+            assert_eq!(line, 1);
+            assert!(message.contains("'Foo'"), "{}", message);
+            assert!(message.contains("\n[main line 2]"), "{}", message);
+            assert!(message.contains("'Bar'"), "{}", message);
+        }
+        other => panic!("expected a compile error, got {:?}", other),
+    }
+}
+
+#[test]
+fn runtime_errors_have_a_stack_trace() {
+    let mut vm = VM::new(Configuration::new());
+    let source = "class A {\n  static fail() { Fiber.abort(\"nope\") }\n}\nA.fail()";
+    match vm.interpret("main", source) {
+        Err(Error::Runtime {
+            message,
+            stack_trace,
+        }) => {
+            assert_eq!(message, "nope");
+            let frame: &Frame = &stack_trace[0];
+            assert_eq!((frame.module.as_str(), frame.line), ("main", 2));
+            assert!(frame.function.contains("fail()"), "{}", frame.function);
+            assert_eq!(stack_trace.last().unwrap().line, 4);
+        }
+        other => panic!("expected a runtime error, got {:?}", other),
+    }
+}
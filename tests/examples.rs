@@ -0,0 +1,94 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+// The `// expect:` comments of `scripts`, taken in the order the scripts
+// print.
+fn expectations(dir: &Path, scripts: &[&str]) -> Vec<String> {
+    let mut expected = Vec::new();
+    for script in scripts {
+        let source = fs::read_to_string(dir.join("src").join(script)).unwrap();
+        for line in source.lines() {
+            if let Some((_, text)) = line.split_once("// expect: ") {
+                expected.push(text.trim_end().to_string());
+            }
+        }
+    }
+    expected
+}
+
+// Runs the example with cargo and checks that every expectation shows up in
+// its output, in order. Other output in between is fine, and so are examples
+// that assert on their output themselves and print nothing.
+fn run_example(name: &str, scripts: &[&str]) {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let dir = root.join("examples").join(name);
+    let output = Command::new(env!("CARGO"))
+        .args(["run", "--quiet"])
+        .env("CARGO_TARGET_DIR", root.join("target").join("examples"))
+        .current_dir(&dir)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "example {} failed:\n{}{}",
+        name,
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let mut lines = stdout.lines();
+    for expected in expectations(&dir, scripts) {
+        assert!(
+            lines.any(|line| line == expected),
+            "`{}` is missing from the output of {}:\n{}",
+            expected,
+            name,
+            stdout
+        );
+    }
+}
+
+#[test]
+fn call() {
+    run_example("call", &["call.wren"]);
+}
+
+#[test]
+fn call_calls_foreign() {
+    run_example("call_calls_foreign", &["call_calls_foreign.wren"]);
+}
+
+#[test]
+fn foreign_class() {
+    run_example("foreign_class", &["foreign_class.wren"]);
+}
+
+#[test]
+fn import() {
+    run_example("import", &["lib/greeting.wren", "import.wren"]);
+}
+
+#[test]
+fn serde() {
+    run_example("serde", &["serde.wren"]);
+}
+
+// These capture the output of their scripts and compare it with what the
+// `// expect:` comments say.
+#[test]
+fn async_methods() {
+    run_example("async", &[]);
+}
+
+#[test]
+fn timer() {
+    run_example("timer", &[]);
+}
+
+#[test]
+fn the_rest() {
+    for name in ["fiber", "helloworld", "output", "sandbox", "value"] {
+        run_example(name, &[]);
+    }
+}
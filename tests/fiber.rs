@@ -0,0 +1,181 @@
+use wren_rs::{Configuration, Error, Fiber, Type, VM};
+
+fn get_fn(vm: &mut VM, name: &str) -> wren_rs::Handle {
+    vm.ensure_slots(1);
+    vm.get_variable("main", name, 0).unwrap();
+    vm.get_slot_handle(0)
+}
+
+#[test]
+fn fibers_run_from_the_host() {
+    let mut vm = VM::new(Configuration::new());
+    vm.interpret(
+        "main",
+        "var double = Fn.new {|x| Fiber.yield(x * 2)\n return x }",
+    )
+    .unwrap();
+    let function = get_fn(&mut vm, "double");
+    let fiber = vm.new_fiber(&function).unwrap();
+    assert_eq!(fiber.call::<f64, _>(&mut vm, (21.0,)).unwrap(), 42.0);
+    assert!(!fiber.is_done(&mut vm).unwrap());
+    assert_eq!(fiber.call::<f64, _>(&mut vm, ()).unwrap(), 21.0);
+    assert!(fiber.is_done(&mut vm).unwrap());
+}
+
+#[test]
+fn new_fiber_in_a_foreign_method_is_an_error() {
+    let mut config = Configuration::new();
+    config.register_method("main", "Fibers", true, "spawn(_)", |vm| {
+        let function = vm.get_slot_handle(1);
+        if !matches!(vm.new_fiber(&function), Err(Error::InForeignMethod)) {
+            vm.set_slot_string(0, "expected InForeignMethod");
+            vm.abort_fiber(0);
+        }
+    });
+    let mut vm = VM::new(config);
+    vm.interpret(
+        "main",
+        "class Fibers {\n  foreign static spawn(_)\n}\nFibers.spawn { 1 }",
+    )
+    .unwrap();
+}
+
+#[test]
+fn suspending_returns_null() {
+    let mut vm = VM::new(Configuration::new());
+    vm.interpret(
+        "main",
+        "var pause = Fn.new { Fiber.suspend() }\nvar gen = Fn.new {\n  Fiber.yield(1)\n  Fiber.suspend()\n}",
+    )
+    .unwrap();
+    let pause = get_fn(&mut vm, "pause");
+    vm.call_method::<(), _>(&pause, "call", ()).unwrap();
+    assert!(matches!(
+        vm.call_method::<f64, _>(&pause, "call", ()),
+        Err(Error::Type { .. })
+    ));
+
+    let gen = get_fn(&mut vm, "gen");
+    let fiber = vm.new_fiber(&gen).unwrap();
+    let items: Vec<_> = fiber.iter::<f64>(&mut vm).collect();
+    assert_eq!(items.len(), 2);
+    assert_eq!(*items[0].as_ref().unwrap(), 1.0);
+    assert!(matches!(items[1], Err(Error::Suspended)));
+}
+
+#[test]
+fn transferring_to_a_fiber() {
+    let mut vm = VM::new(Configuration::new());
+    vm.interpret(
+        "main",
+        "var add = Fn.new {|x| x + 1 }\nvar wait = Fn.new { Fiber.yield(1) }",
+    )
+    .unwrap();
+    let add = get_fn(&mut vm, "add");
+    let fiber = vm.new_fiber(&add).unwrap();
+    assert_eq!(fiber.transfer::<f64, _>(&mut vm, (1.0,)).unwrap(), 2.0);
+    assert!(fiber.is_done(&mut vm).unwrap());
+
+    // With nobody to yield to, yielding ends the call.
+    let wait = get_fn(&mut vm, "wait");
+    let fiber = vm.new_fiber(&wait).unwrap();
+    assert_eq!(fiber.transfer::<Option<f64>, _>(&mut vm, ()).unwrap(), None);
+    assert!(!fiber.is_done(&mut vm).unwrap());
+}
+
+#[test]
+fn only_fibers_convert_to_fiber() {
+    let mut vm = VM::new(Configuration::new());
+    vm.interpret("main", "var fiber = Fiber.new {}\nvar fn = Fn.new {}")
+        .unwrap();
+    vm.ensure_slots(1);
+    vm.get_variable("main", "fiber", 0).unwrap();
+    assert!(vm.get::<Fiber>(0).is_ok());
+    vm.get_variable("main", "fn", 0).unwrap();
+    assert!(matches!(
+        vm.get::<Fiber>(0),
+        Err(Error::NotFiber {
+            found: Type::Unknown
+        })
+    ));
+    vm.set_slot_double(0, 1.0);
+    assert!(matches!(
+        vm.get::<Fiber>(0),
+        Err(Error::NotFiber { found: Type::Num })
+    ));
+}
+
+fn generator(vm: &mut VM, name: &str, body: &str) -> wren_rs::Handle {
+    let source = format!("var {} = Fiber.new {{\n{}\n}}", name, body);
+    vm.interpret("main", source).unwrap();
+    get_fn(vm, name)
+}
+
+#[test]
+fn fiber_iter_yields_until_done() {
+    let mut vm = VM::new(Configuration::new());
+    let fiber = generator(
+        &mut vm,
+        "numbers",
+        "for (i in 1..3) Fiber.yield(i)\nreturn \"not an item\"",
+    );
+    let items: Result<Vec<f64>, Error> = vm.fiber_iter(&fiber).collect();
+    assert_eq!(items.unwrap(), [1.0, 2.0, 3.0]);
+    // A finished fiber has nothing left.
+    assert_eq!(vm.fiber_iter::<f64>(&fiber).count(), 0);
+}
+
+#[test]
+fn fiber_iter_skips_past_bad_items() {
+    let mut vm = VM::new(Configuration::new());
+    let fiber = generator(&mut vm, "mixed", "Fiber.yield(\"one\")\nFiber.yield(2)");
+    let mut iter = vm.fiber_iter::<f64>(&fiber);
+    assert!(matches!(
+        iter.next(),
+        Some(Err(Error::Type {
+            expected: Type::Num,
+            found: Type::String
+        }))
+    ));
+    assert_eq!(iter.next().unwrap().unwrap(), 2.0);
+    assert!(iter.next().is_none());
+}
+
+#[test]
+fn fiber_iter_ends_on_errors_and_suspension() {
+    let mut vm = VM::new(Configuration::new());
+    let failing = generator(
+        &mut vm,
+        "failing",
+        "Fiber.yield(1)\nFiber.abort(\"broken\")",
+    );
+    let mut iter = vm.fiber_iter::<f64>(&failing);
+    assert_eq!(iter.next().unwrap().unwrap(), 1.0);
+    match iter.next() {
+        Some(Err(Error::Runtime { message, .. })) => assert_eq!(message, "broken"),
+        other => panic!("expected a runtime error, got {:?}", other),
+    }
+    assert!(iter.next().is_none());
+
+    let suspending = generator(&mut vm, "suspending", "Fiber.yield(1)\nFiber.suspend()");
+    let mut iter = vm.fiber_iter::<f64>(&suspending);
+    assert_eq!(iter.next().unwrap().unwrap(), 1.0);
+    assert!(matches!(iter.next(), Some(Err(Error::Suspended))));
+    assert!(iter.next().is_none());
+}
+
+#[test]
+fn fibers_from_new_fiber_can_be_iterated() {
+    let mut vm = VM::new(Configuration::new());
+    vm.interpret(
+        "main",
+        "var count = Fn.new {|n| (1..n).each {|i| Fiber.yield(i) } }",
+    )
+    .unwrap();
+    let function = get_fn(&mut vm, "count");
+    let fiber = vm.new_fiber(&function).unwrap();
+    // The first resume passes nothing, so start it by hand.
+    assert_eq!(fiber.call::<f64, _>(&mut vm, (2.0,)).unwrap(), 1.0);
+    let rest: Vec<f64> = fiber.iter(&mut vm).collect::<Result<_, _>>().unwrap();
+    assert_eq!(rest, [2.0]);
+}
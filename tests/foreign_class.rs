@@ -0,0 +1,72 @@
+use wren_rs::{
+    wren_bind_foreign_class_fn, wren_foreign_method_fn, ClassMethods, Configuration, ForeignClass,
+    ForeignClassMethods, VM,
+};
+
+struct Counter(u32);
+
+impl ForeignClass for Counter {
+    fn construct(_: &mut VM) -> Result<Self, String> {
+        Ok(Counter(7))
+    }
+    fn methods(methods: &mut ClassMethods<Self>) {
+        methods.static_method("valueOf(_)", |vm| {
+            match vm.get_slot_foreign_class::<Counter>(1) {
+                Some(mut counter) => {
+                    let value = counter.inner().0;
+                    vm.set_slot_double(0, value as f64)
+                }
+                None => vm.set_slot_null(0),
+            }
+        });
+    }
+}
+
+struct Other;
+
+impl ForeignClass for Other {
+    fn construct(_: &mut VM) -> Result<Self, String> {
+        Ok(Other)
+    }
+}
+
+// A single byte of foreign data, too small to hold a pointer.
+fn allocate_byte(vm: &mut VM) {
+    vm.set_slot_new_foreign::<u8>(0, 0);
+}
+
+fn bind_raw(_: &mut VM, _: &str, _: &str) -> ForeignClassMethods {
+    ForeignClassMethods {
+        allocate: wren_foreign_method_fn!(allocate_byte),
+        finalize: None,
+    }
+}
+
+#[test]
+fn only_objects_of_the_class_are_handed_out() {
+    let mut config = Configuration::new();
+    config.register_class::<Counter>("main", "Counter");
+    config.register_class::<Other>("main", "Other");
+    config.set_bind_foreign_class_fn(wren_bind_foreign_class_fn!(bind_raw));
+    config.capture_output();
+    let mut vm = VM::new(config);
+    vm.interpret(
+        "main",
+        "foreign class Counter {
+  construct new() {}
+  foreign static valueOf(_)
+}
+foreign class Other {
+  construct new() {}
+}
+foreign class Raw {
+  construct new() {}
+}
+System.print(Counter.valueOf(Counter.new()))
+System.print(Counter.valueOf(Other.new()))
+System.print(Counter.valueOf(Raw.new()))
+System.print(Counter.valueOf(1))",
+    )
+    .unwrap();
+    assert_eq!(vm.take_output(), "7\nnull\nnull\nnull\n");
+}
@@ -0,0 +1,148 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use wren_rs::{Configuration, Error, ForeignMethodFn, MAX_FOREIGN_METHODS, VM};
+
+#[test]
+fn closures_keep_their_state() {
+    let calls = Rc::new(Cell::new(0));
+    let mut config = Configuration::new();
+    let counter = calls.clone();
+    config.register_method("main", "Counter", true, "bump()", move |vm| {
+        counter.set(counter.get() + 1);
+        vm.set_slot_double(0, counter.get() as f64);
+    });
+    let mut vm = VM::new(config);
+    vm.interpret(
+        "main",
+        "class Counter {\n  foreign static bump()\n}\nCounter.bump()\nCounter.bump()",
+    )
+    .unwrap();
+    assert_eq!(calls.get(), 2);
+}
+
+#[test]
+fn dispatch_table_has_a_fixed_size() {
+    let mut vm = VM::new(Configuration::new());
+    for _ in 0..MAX_FOREIGN_METHODS {
+        vm.foreign_method(|_| {}).unwrap();
+    }
+    match vm.foreign_method(|_| {}) {
+        Err(Error::MethodLimit { limit }) => assert_eq!(limit, MAX_FOREIGN_METHODS),
+        other => panic!("expected MethodLimit, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn removed_closures_free_their_slot() {
+    let mut vm = VM::new(Configuration::new());
+    let methods: Vec<ForeignMethodFn> = (0..MAX_FOREIGN_METHODS)
+        .map(|_| vm.foreign_method(|_| {}).unwrap())
+        .collect();
+    assert!(vm.remove_foreign_method(methods[7]));
+    assert!(!vm.remove_foreign_method(methods[7]));
+    assert!(!vm.remove_foreign_method(None));
+    // Binding again reuses the slot.
+    let address = |f: ForeignMethodFn| f.map(|f| f as usize);
+    let again = vm.foreign_method(|_| {}).unwrap();
+    assert_eq!(address(again), address(methods[7]));
+    assert!(vm.foreign_method(|_| {}).is_err());
+}
+
+thread_local! {
+    static BORROWED: std::cell::Cell<ForeignMethodFn> = const { std::cell::Cell::new(None) };
+}
+
+fn bind_borrowed(_: &mut VM, _: &str, _: &str, _: bool, _: &str) -> ForeignMethodFn {
+    BORROWED.with(|f| f.get())
+}
+
+#[test]
+fn closures_of_other_vms_are_refused() {
+    let mut owner = VM::new(Configuration::new());
+    for _ in 0..3 {
+        owner.foreign_method(|_| {}).unwrap();
+    }
+    BORROWED.with(|f| f.set(owner.foreign_method(|_| {}).unwrap()));
+    let mut config = Configuration::new();
+    config.set_bind_foreign_method_fn(wren_rs::wren_bind_foreign_method_fn!(bind_borrowed));
+    let mut vm = VM::new(config);
+    match vm.interpret("main", "class B {\n  foreign static m()\n}\nB.m()") {
+        Err(Error::Runtime { message, .. }) => {
+            assert_eq!(message, "Foreign method is not bound to this VM")
+        }
+        other => panic!("expected a runtime error, got {:?}", other),
+    }
+}
+
+#[test]
+fn registered_methods_past_the_limit_are_reported_as_missing() {
+    let mut config = Configuration::new();
+    let mut source = String::from("class Many {\n");
+    for i in 0..=MAX_FOREIGN_METHODS {
+        config.register_method("main", "Many", true, &format!("m{}()", i), |_| {});
+        source.push_str(&format!("  foreign static m{}()\n", i));
+    }
+    source.push('}');
+    let mut vm = VM::new(config);
+    match vm.interpret("main", source) {
+        Err(Error::Runtime { message, .. }) => {
+            assert!(message.contains("m256()"), "{}", message)
+        }
+        other => panic!("expected a runtime error, got {:?}", other),
+    }
+}
+
+#[test]
+fn missing_methods_list_what_is_registered() {
+    let mut config = Configuration::new();
+    config.register_method("main", "Greeter", true, "hello()", |_| {});
+    let mut vm = VM::new(config);
+    match vm.interpret("main", "class Greeter {\n  foreign static hallo()\n}") {
+        Err(Error::Runtime { message, .. }) => assert!(
+            message.contains(
+                "No foreign method registered for `static Greeter.hallo()` in module \"main\" \
+                 (registered: static Greeter.hello())"
+            ),
+            "{}",
+            message
+        ),
+        other => panic!("expected a runtime error, got {:?}", other),
+    }
+}
+
+#[test]
+fn maps_cannot_be_read_inside_foreign_methods() {
+    use std::collections::HashMap;
+
+    let mut config = Configuration::new();
+    config.register_method("main", "Reader", true, "read(_)", |vm| {
+        let result = vm.get::<HashMap<String, f64>>(1);
+        vm.set_slot_bool(0, matches!(result, Err(Error::InForeignMethod)));
+    });
+    config.capture_output();
+    let mut vm = VM::new(config);
+    vm.interpret(
+        "main",
+        "class Reader {\n  foreign static read(_)\n}\nSystem.print(Reader.read({\"a\": 1}))",
+    )
+    .unwrap();
+    assert_eq!(vm.take_output(), "true\n");
+}
+
+#[test]
+fn panics_become_runtime_errors() {
+    let mut config = Configuration::new();
+    config.register_method("main", "Boom", true, "go()", |_| panic!("boom"));
+    config.capture_output();
+    let mut vm = VM::new(config);
+    vm.interpret(
+        "main",
+        "class Boom {\n  foreign static go()\n}\nSystem.print(Fiber.new { Boom.go() }.try())",
+    )
+    .unwrap();
+    assert_eq!(vm.take_output(), "boom\n");
+    match vm.interpret("main", "Boom.go()") {
+        Err(Error::Runtime { message, .. }) => assert_eq!(message, "boom"),
+        other => panic!("expected a runtime error, got {:?}", other),
+    }
+}
@@ -0,0 +1,49 @@
+use wren_rs::{Configuration, Error, VM};
+
+fn vm_with_class() -> VM {
+    let mut vm = VM::new(Configuration::new());
+    vm.interpret("main", "class Box {\n  static value { 42 }\n}")
+        .unwrap();
+    vm
+}
+
+fn class_handle(vm: &mut VM) -> wren_rs::Handle {
+    vm.ensure_slots(1);
+    vm.get_variable("main", "Box", 0).unwrap();
+    vm.get_slot_handle(0)
+}
+
+#[test]
+fn handles_work_with_their_own_vm() {
+    let mut vm = vm_with_class();
+    let class = class_handle(&mut vm);
+    let value: f64 = vm.call_method(&class, "value", ()).unwrap();
+    assert_eq!(value, 42.0);
+}
+
+#[test]
+fn handles_are_rejected_by_other_vms() {
+    let mut first = vm_with_class();
+    let mut second = vm_with_class();
+    let class = class_handle(&mut first);
+    let method = first.make_call_handle("value").unwrap();
+    assert!(matches!(
+        second.call_method::<f64, _>(&class, "value", ()),
+        Err(Error::WrongVm)
+    ));
+    assert!(matches!(second.call(&method), Err(Error::WrongVm)));
+}
+
+#[test]
+fn handles_outlive_their_vm() {
+    let mut vm = vm_with_class();
+    let class = class_handle(&mut vm);
+    drop(vm);
+    let mut other = vm_with_class();
+    assert!(matches!(
+        other.call_method::<f64, _>(&class, "value", ()),
+        Err(Error::StaleHandle)
+    ));
+    // Dropping it now must not touch the freed VM.
+    drop(class);
+}
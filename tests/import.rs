@@ -0,0 +1,130 @@
+// Every import hands wren a resolved name and a source allocated by the crate
+// and freed by wren. To check that under AddressSanitizer:
+//
+//     RUSTFLAGS=-Zsanitizer=address cargo +nightly test --test import \
+//         --target x86_64-unknown-linux-gnu
+
+use std::fs;
+use std::path::PathBuf;
+use wren_rs::{
+    ChainLoader, Configuration, Error, FileLoader, MemoryLoader, ModuleLoader, Source, VM,
+};
+
+fn vm_with<L: ModuleLoader + 'static>(loader: L) -> VM {
+    let mut config = Configuration::new();
+    config.set_module_loader(loader);
+    config.capture_output();
+    VM::new(config)
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("wren-rs-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn imports_from_memory() {
+    let mut loader = MemoryLoader::new();
+    loader
+        .insert("a", "import \"b\" for B\nvar A = \"a\" + B\n")
+        .insert("b", "var B = \"b\"\n");
+    let mut vm = vm_with(loader);
+    // Importing a module twice only loads it once.
+    vm.interpret(
+        "main",
+        "import \"a\" for A\nimport \"b\" for B\nSystem.print(A + B)",
+    )
+    .unwrap();
+    assert_eq!(vm.take_output(), "abb\n");
+}
+
+#[test]
+fn imports_from_files() {
+    let dir = temp_dir("files");
+    fs::write(dir.join("greeting.wren"), "var Greeting = \"hello\"\n").unwrap();
+    fs::create_dir_all(dir.join("pkg")).unwrap();
+    fs::write(
+        dir.join("pkg/pkg.wren"),
+        "import \"./helper\" for Name\nvar Package = Name\n",
+    )
+    .unwrap();
+    fs::write(dir.join("pkg/helper.wren"), "var Name = \"pkg\"\n").unwrap();
+
+    let mut vm = vm_with(FileLoader::new().with_modules_dir(&dir));
+    let result = vm.interpret(
+        "main",
+        "import \"greeting\" for Greeting\nimport \"pkg\" for Package\nSystem.print(Greeting + \" \" + Package)",
+    );
+    fs::remove_dir_all(&dir).unwrap();
+    result.unwrap();
+    assert_eq!(vm.take_output(), "hello pkg\n");
+}
+
+#[test]
+fn missing_modules_are_runtime_errors() {
+    let mut vm = vm_with(MemoryLoader::new());
+    match vm.interpret("main", "import \"nowhere\"") {
+        Err(Error::Runtime { message, .. }) => assert!(message.contains("nowhere"), "{}", message),
+        other => panic!("expected a runtime error, got {:?}", other),
+    }
+}
+
+struct Refusing;
+
+impl ModuleLoader for Refusing {
+    fn resolve(&self, _: &str, name: &str) -> Option<String> {
+        if name.starts_with("secret") {
+            None
+        } else {
+            Some(name.to_string())
+        }
+    }
+    fn load(&self, name: &str) -> Option<Source> {
+        Some(Source::Owned(format!("var Name = \"{}\"\n", name)))
+    }
+}
+
+#[test]
+fn failed_resolves_are_runtime_errors() {
+    let mut vm = vm_with(Refusing);
+    vm.interpret("main", "import \"open\" for Name\nSystem.print(Name)")
+        .unwrap();
+    assert_eq!(vm.take_output(), "open\n");
+    match vm.interpret("main", "import \"secret\"") {
+        Err(Error::Runtime { message, .. }) => assert!(message.contains("secret"), "{}", message),
+        other => panic!("expected a runtime error, got {:?}", other),
+    }
+}
+
+#[test]
+fn relative_imports_follow_the_importing_file() {
+    // A path relative to the working directory, which cargo sets to the
+    // package root.
+    let dir = format!("target/wren-rs-relative-{}", std::process::id());
+    fs::create_dir_all(format!("{}/modules/pkg", dir)).unwrap();
+    fs::write(format!("{}/lib.wren", dir), "var Lib = \"lib\"\n").unwrap();
+    fs::write(
+        format!("{}/main.wren", dir),
+        "import \"./lib\" for Lib\nimport \"pkg\" for Package\nSystem.print(Lib + \" \" + Package)",
+    )
+    .unwrap();
+    fs::write(
+        format!("{}/modules/pkg/pkg.wren", dir),
+        "import \"./helper\" for Name\nvar Package = Name\n",
+    )
+    .unwrap();
+    fs::write(
+        format!("{}/modules/pkg/helper.wren", dir),
+        "var Name = \"pkg\"\n",
+    )
+    .unwrap();
+
+    // Through a chain, the package is resolved by the loader that found it.
+    let files = FileLoader::new().with_modules_dir(format!("{}/modules", dir));
+    let mut vm = vm_with(ChainLoader::new().with(MemoryLoader::new()).with(files));
+    let result = vm.read_file(&format!("{}/main.wren", dir));
+    fs::remove_dir_all(&dir).unwrap();
+    result.unwrap();
+    assert_eq!(vm.take_output(), "lib pkg\n");
+}
@@ -0,0 +1,56 @@
+use wren_rs::{Configuration, Error, VM};
+
+fn vm_with_list() -> VM {
+    let mut vm = VM::new(Configuration::new());
+    vm.ensure_slots(2);
+    vm.set_slot_list_from_iter(0, [1.0, 2.0, 3.0]);
+    vm
+}
+
+#[test]
+fn bad_slots_and_indices_are_errors() {
+    let mut vm = vm_with_list();
+    assert!(matches!(
+        vm.get_list_element(0, 0, 5),
+        Err(Error::Slot { slot: 5, count: 2 })
+    ));
+    assert!(matches!(
+        vm.get_list_element(7, 0, 1),
+        Err(Error::Slot { slot: 7, .. })
+    ));
+    assert!(matches!(
+        vm.get_list_element(0, 3, 1),
+        Err(Error::Index { index: 3, count: 3 })
+    ));
+    vm.get_list_element(0, -1, 1).unwrap();
+    assert_eq!(vm.get_slot_double(1), Some(3.0));
+}
+
+#[test]
+fn removing_and_clearing() {
+    let mut vm = vm_with_list();
+    vm.remove_from_list(0, 0, 1).unwrap();
+    assert_eq!(vm.get_slot_double(1), Some(1.0));
+    assert_eq!(vm.get_list_count(0), 2);
+    vm.clear_list(0).unwrap();
+    assert_eq!(vm.get_list_count(0), 0);
+}
+
+#[test]
+fn removing_needs_wren_outside_foreign_methods() {
+    let mut config = Configuration::new();
+    config.register_method("main", "Lists", true, "pop(_)", |vm| {
+        vm.ensure_slots(2);
+        let result = vm.remove_from_list(1, -1, 0);
+        if !matches!(result, Err(Error::InForeignMethod)) {
+            vm.set_slot_string(0, "expected InForeignMethod");
+            vm.abort_fiber(0);
+        }
+    });
+    let mut vm = VM::new(config);
+    vm.interpret(
+        "main",
+        "class Lists {\n  foreign static pop(_)\n}\nLists.pop([1])",
+    )
+    .unwrap();
+}
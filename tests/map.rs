@@ -0,0 +1,80 @@
+use std::collections::{BTreeMap, HashMap};
+use wren_rs::{Configuration, Error, Type, VM};
+
+fn vm_with(source: &str) -> VM {
+    let mut vm = VM::new(Configuration::new());
+    vm.interpret("main", source).unwrap();
+    vm.ensure_slots(1);
+    vm.get_variable("main", "map", 0).unwrap();
+    vm
+}
+
+#[test]
+fn map_entries_enumerates_every_entry() {
+    let mut vm = vm_with("var map = {\"a\": 1, \"b\": 2, \"c\": 3}");
+    let mut entries: Vec<(String, f64)> = vm.map_entries(0).unwrap();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(
+        entries,
+        [
+            ("a".to_string(), 1.0),
+            ("b".to_string(), 2.0),
+            ("c".to_string(), 3.0)
+        ]
+    );
+    assert_eq!(vm.get_slot_count(), 1);
+}
+
+#[test]
+fn maps_convert_to_hash_and_btree_maps() {
+    let mut vm = vm_with("var map = {1: [true], 2: [], 3: [false, true]}");
+    let hash: HashMap<i32, Vec<bool>> = vm.get(0).unwrap();
+    assert_eq!(hash.len(), 3);
+    assert_eq!(hash[&3], [false, true]);
+    let btree: BTreeMap<i32, Vec<bool>> = vm.get(0).unwrap();
+    assert_eq!(btree.keys().copied().collect::<Vec<_>>(), [1, 2, 3]);
+    assert_eq!(btree.into_iter().collect::<HashMap<_, _>>(), hash);
+}
+
+#[test]
+fn rust_maps_round_trip() {
+    let mut config = Configuration::new();
+    config.capture_output();
+    let mut vm = VM::new(config);
+    let map: HashMap<String, f64> = [("a".to_string(), 1.0), ("b".to_string(), 2.0)]
+        .into_iter()
+        .collect();
+    let btree: BTreeMap<String, String> =
+        [("x".to_string(), "y".to_string())].into_iter().collect();
+    vm.ensure_slots(2);
+    vm.set(0, &btree);
+    vm.set(1, &map);
+    assert_eq!(vm.get_slot_count(), 2);
+    assert_eq!(vm.get::<BTreeMap<String, String>>(0).unwrap(), btree);
+    assert_eq!(vm.get::<HashMap<String, f64>>(1).unwrap(), map);
+
+    // Scripts get an ordinary map.
+    vm.interpret(
+        "main",
+        "var show = Fn.new {|map| System.print(map[\"b\"]) }",
+    )
+    .unwrap();
+    vm.ensure_slots(2);
+    vm.get_variable("main", "show", 0).unwrap();
+    vm.set(1, &map);
+    let call = vm.make_call_handle("call(_)").unwrap();
+    vm.call(&call).unwrap();
+    assert_eq!(vm.take_output(), "2\n");
+}
+
+#[test]
+fn entries_of_the_wrong_type_are_errors() {
+    let mut vm = vm_with("var map = {\"a\": 1, \"b\": \"two\"}");
+    match vm.get::<HashMap<String, f64>>(0) {
+        Err(Error::Type { expected, found }) => {
+            assert_eq!((expected, found), (Type::Num, Type::String))
+        }
+        other => panic!("expected a type error, got {:?}", other),
+    }
+    assert_eq!(vm.get_slot_count(), 1);
+}
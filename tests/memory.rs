@@ -0,0 +1,37 @@
+use wren_rs::{Configuration, VM};
+
+#[test]
+fn collect_garbage_reports_what_it_freed() {
+    let mut vm = VM::new(Configuration::new());
+    vm.interpret("main", "var garbage = List.filled(100000, 0)")
+        .unwrap();
+    let kept = vm.collect_garbage();
+    assert_eq!(kept.after, vm.memory_stats().current);
+
+    vm.interpret("main", "garbage = null").unwrap();
+    let stats = vm.collect_garbage();
+    assert!(stats.before >= kept.after);
+    // A list of 100000 values takes at least 8 bytes per element.
+    assert!(stats.freed() >= 800000, "{:?}", stats);
+    assert_eq!(stats.after, vm.memory_stats().current);
+    assert!(vm.memory_stats().peak >= stats.before);
+}
+
+fn peak_while_making_garbage(config: Configuration) -> usize {
+    let mut vm = VM::new(config);
+    vm.interpret("main", "for (i in 0...5000) List.filled(100, i)")
+        .unwrap();
+    vm.memory_stats().peak
+}
+
+#[test]
+fn heap_options_make_collections_happen_sooner() {
+    // With the default 10MB first collection, all of the garbage piles up.
+    let untuned = peak_while_making_garbage(Configuration::new());
+    let mut config = Configuration::new();
+    config.set_initial_heap_size(64 * 1024);
+    config.set_min_heap_size(32 * 1024);
+    config.set_heap_growth_percent(25);
+    let tuned = peak_while_making_garbage(config);
+    assert!(tuned < untuned / 2, "{} vs {}", tuned, untuned);
+}
@@ -0,0 +1,48 @@
+use wren_rs::{Configuration, VM};
+
+fn vm() -> VM {
+    let mut config = Configuration::new();
+    config.capture_output();
+    VM::new(config)
+}
+
+#[cfg(feature = "meta")]
+#[test]
+fn meta_is_importable() {
+    let mut vm = vm();
+    vm.interpret(
+        "main",
+        "import \"meta\" for Meta\nMeta.eval(\"System.print(1 + 2)\")",
+    )
+    .unwrap();
+    assert_eq!(vm.take_output(), "3\n");
+}
+
+#[cfg(not(feature = "meta"))]
+#[test]
+fn meta_is_left_out() {
+    let mut vm = vm();
+    assert!(vm.interpret("main", "import \"meta\" for Meta").is_err());
+}
+
+#[cfg(feature = "random")]
+#[test]
+fn seeded_random_is_deterministic() {
+    let mut vm = vm();
+    let source = "import \"random\" for Random
+var random = Random.new(12345)
+System.print([random.int(1000), random.int(1000), random.float()])";
+    vm.interpret("main", source).unwrap();
+    let first = vm.take_output();
+    vm.interpret("again", source).unwrap();
+    assert_eq!(vm.take_output(), first);
+}
+
+#[cfg(not(feature = "random"))]
+#[test]
+fn random_is_left_out() {
+    let mut vm = vm();
+    assert!(vm
+        .interpret("main", "import \"random\" for Random")
+        .is_err());
+}
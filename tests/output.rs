@@ -0,0 +1,68 @@
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+use wren_rs::{Configuration, VM};
+
+// Records every write it gets, so tests can see how the output was split up.
+#[derive(Clone, Default)]
+struct Sink(Rc<RefCell<Vec<String>>>);
+
+impl Sink {
+    fn text(&self) -> String {
+        self.0.borrow().concat()
+    }
+}
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .borrow_mut()
+            .push(String::from_utf8_lossy(buf).into_owned());
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn writer_gets_everything_right_away() {
+    let sink = Sink::default();
+    let mut config = Configuration::new();
+    config.set_writer(sink.clone());
+    let mut vm = VM::new(config);
+    vm.interpret("main", "System.write(\"a\")").unwrap();
+    assert_eq!(sink.text(), "a");
+    vm.interpret("main", "System.print(\"b\")").unwrap();
+    assert_eq!(sink.text(), "ab\n");
+    assert_eq!(vm.take_output(), "");
+}
+
+#[test]
+fn line_writer_holds_back_partial_lines() {
+    let sink = Sink::default();
+    let mut config = Configuration::new();
+    config.set_line_writer(sink.clone());
+    let mut vm = VM::new(config);
+    vm.interpret("main", "System.write(\"a\")").unwrap();
+    assert_eq!(sink.text(), "");
+    vm.interpret("main", "System.write(\"b\\nc\")").unwrap();
+    assert_eq!(sink.text(), "ab\n");
+    vm.flush_output();
+    assert_eq!(sink.text(), "ab\nc");
+}
+
+#[test]
+fn every_vm_has_its_own_output() {
+    let mut first = Configuration::new();
+    first.capture_output();
+    let mut first = VM::new(first);
+    let mut second = Configuration::new();
+    second.capture_output();
+    let mut second = VM::new(second);
+    first.interpret("main", "System.print(1)").unwrap();
+    second.interpret("main", "System.print(2)").unwrap();
+    assert_eq!(first.take_output(), "1\n");
+    assert_eq!(second.take_output(), "2\n");
+    assert_eq!(first.take_output(), "");
+}
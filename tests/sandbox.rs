@@ -0,0 +1,101 @@
+use std::thread;
+use std::time::Duration;
+use wren_rs::{Configuration, Error, VM};
+
+fn runtime_message(result: Result<(), Error>) -> String {
+    match result {
+        Err(Error::Runtime { message, .. }) => message,
+        other => panic!("expected a runtime error, got {:?}", other),
+    }
+}
+
+#[test]
+fn memory_limit_refuses_the_allocation() {
+    let mut config = Configuration::new();
+    config.set_memory_limit(1024 * 1024);
+    let mut vm = VM::new(config);
+    let message = runtime_message(vm.interpret("main", "var big = List.filled(1000000, 0)"));
+    assert_eq!(message, "Out of memory.");
+    assert!(vm.memory_stats().peak <= 1024 * 1024);
+
+    // A script can catch it, and the VM stays usable.
+    vm.interpret(
+        "main",
+        "var error = Fiber.new { List.filled(1000000, 0) }.try()
+if (error != \"Out of memory.\") Fiber.abort(error)",
+    )
+    .unwrap();
+    vm.interpret("main", "var small = List.filled(10, 0)")
+        .unwrap();
+    assert!(vm.memory_stats().peak <= 1024 * 1024);
+}
+
+#[test]
+fn interrupts_are_per_vm() {
+    // A VM without limits on one thread is not affected by the budget of a
+    // VM on another.
+    let limited = thread::spawn(|| {
+        let mut config = Configuration::new();
+        config.set_time_limit(Duration::from_millis(50));
+        let mut vm = VM::new(config);
+        runtime_message(vm.interpret("main", "while (true) {}"))
+    });
+    let unlimited = thread::spawn(|| {
+        let mut vm = VM::new(Configuration::new());
+        vm.interpret("main", "var n = 0\nfor (i in 0...3000000) n = n + i")
+    });
+    assert_eq!(
+        limited.join().unwrap(),
+        "Execution interrupted: time limit exceeded."
+    );
+    unlimited.join().unwrap().unwrap();
+}
+
+#[test]
+fn step_limit_cannot_be_caught() {
+    let mut config = Configuration::new();
+    config.set_step_limit(10000);
+    let mut vm = VM::new(config);
+    let message = runtime_message(vm.interpret(
+        "main",
+        "var error = Fiber.new { while (true) {} }.try()\nwhile (true) {}",
+    ));
+    assert_eq!(message, "Execution interrupted: step limit exceeded.");
+    // Every interpret starts with a fresh budget.
+    vm.interpret("main", "for (i in 0...100) {}").unwrap();
+    vm.set_step_limit(None);
+    vm.interpret("main", "for (i in 0...100000) {}").unwrap();
+}
+
+#[test]
+fn interrupt_handle_stops_a_running_script() {
+    let mut vm = VM::new(Configuration::new());
+    let handle = vm.interrupt_handle();
+    let interrupter = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        handle.interrupt();
+    });
+    let message = runtime_message(vm.interpret("main", "while (true) {}"));
+    assert_eq!(message, "Execution interrupted.");
+    interrupter.join().unwrap();
+    vm.interpret("main", "var n = 1 + 2").unwrap();
+}
+
+#[test]
+fn interrupting_an_idle_vm_stops_the_next_script() {
+    let mut vm = VM::new(Configuration::new());
+    vm.interrupt_handle().interrupt();
+    let message = runtime_message(vm.interpret("main", "var n = 1 + 2"));
+    assert_eq!(message, "Execution interrupted.");
+    vm.interpret("main", "var m = 1 + 2").unwrap();
+}
+
+#[test]
+fn time_limit_can_be_changed_between_runs() {
+    let mut vm = VM::new(Configuration::new());
+    vm.set_time_limit(Some(Duration::from_millis(20)));
+    let message = runtime_message(vm.interpret("main", "while (true) {}"));
+    assert_eq!(message, "Execution interrupted: time limit exceeded.");
+    vm.set_time_limit(None);
+    vm.interpret("main", "for (i in 0...100000) {}").unwrap();
+}
@@ -0,0 +1,101 @@
+#![cfg(feature = "serde")]
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use wren_rs::{Configuration, Error, VM};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Server {
+    host: String,
+    port: u16,
+    backup: Option<bool>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Limit {
+    None,
+    Bytes(u64),
+    Range(u32, u32),
+    Timeout { seconds: u32 },
+}
+
+fn round_trip<T: Serialize + for<'de> Deserialize<'de>>(vm: &mut VM, value: &T) -> T {
+    vm.ensure_slots(1);
+    vm.set_serialize(0, value).unwrap();
+    vm.get_deserialize(0).unwrap()
+}
+
+#[test]
+fn values_survive_a_round_trip() {
+    let mut vm = VM::new(Configuration::new());
+    let servers = vec![
+        Server {
+            host: "a".to_string(),
+            port: 80,
+            backup: Some(true),
+        },
+        Server {
+            host: "b".to_string(),
+            port: 8080,
+            backup: None,
+        },
+    ];
+    assert_eq!(round_trip(&mut vm, &servers), servers);
+    for limit in [
+        Limit::None,
+        Limit::Bytes(1024),
+        Limit::Range(1, 2),
+        Limit::Timeout { seconds: 5 },
+    ] {
+        assert_eq!(round_trip(&mut vm, &limit), limit);
+    }
+    let map: BTreeMap<String, (f64, bool)> = [("x".to_string(), (1.5, true))].into_iter().collect();
+    assert_eq!(round_trip(&mut vm, &map), map);
+    // Only the slot asked for is used.
+    assert_eq!(vm.get_slot_count(), 1);
+}
+
+// What a script prints for `value`.
+fn shown<T: Serialize>(value: &T) -> String {
+    let mut config = Configuration::new();
+    config.capture_output();
+    let mut vm = VM::new(config);
+    vm.interpret("main", "var show = Fn.new {|value| System.write(value) }")
+        .unwrap();
+    vm.ensure_slots(2);
+    vm.get_variable("main", "show", 0).unwrap();
+    vm.set_serialize(1, value).unwrap();
+    let call = vm.make_call_handle("call(_)").unwrap();
+    vm.call(&call).unwrap();
+    vm.take_output()
+}
+
+#[test]
+fn scripts_see_maps_lists_and_tags() {
+    let server = shown(&Server {
+        host: "a".to_string(),
+        port: 80,
+        backup: None,
+    });
+    assert!(server.contains("host: a"), "{}", server);
+    assert!(server.contains("backup: null"), "{}", server);
+    assert_eq!(shown(&Limit::None), "None");
+    assert_eq!(shown(&Limit::Bytes(7)), "{Bytes: 7}");
+    assert_eq!(shown(&vec![1, 2]), "[1, 2]");
+}
+
+#[test]
+fn errors_name_the_path() {
+    let mut vm = VM::new(Configuration::new());
+    vm.interpret(
+        "main",
+        "var servers = [{\"host\": \"a\", \"port\": 80}, {\"host\": \"b\", \"port\": \"eighty\"}]",
+    )
+    .unwrap();
+    vm.ensure_slots(1);
+    vm.get_variable("main", "servers", 0).unwrap();
+    match vm.get_deserialize::<Vec<Server>>(0) {
+        Err(Error::Serde { path, .. }) => assert_eq!(path, "[1].port"),
+        other => panic!("expected a serde error, got {:?}", other),
+    }
+}
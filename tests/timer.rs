@@ -0,0 +1,115 @@
+use std::time::Duration;
+
+use wren_rs::{Configuration, Error, VM};
+
+fn timer_vm() -> VM {
+    let mut config = Configuration::new();
+    config.capture_output();
+    config.enable_scheduler();
+    config.use_virtual_clock();
+    VM::new(config)
+}
+
+#[test]
+fn the_scheduler_is_opt_in() {
+    let mut vm = VM::new(Configuration::new());
+    assert!(vm.interpret("main", "import \"timer\" for Timer").is_err());
+    let mut vm = timer_vm();
+    vm.interpret("main", "import \"timer\" for Timer").unwrap();
+}
+
+#[test]
+fn sleeps_must_be_finite() {
+    let mut vm = timer_vm();
+    vm.interpret("main", "import \"timer\" for Timer").unwrap();
+    for delay in ["1/0", "0/0"] {
+        let error = vm
+            .interpret("main", format!("Timer.sleep({})", delay))
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("Milliseconds must be finite."), "{}", error);
+    }
+    let error = vm
+        .interpret("main", "Timer.sleep(1e300)")
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("Milliseconds is too large."), "{}", error);
+    assert_eq!(vm.pending_timers(), 0);
+}
+
+#[test]
+fn only_a_virtual_clock_advances() {
+    let mut config = Configuration::new();
+    config.enable_scheduler();
+    let mut vm = VM::new(config);
+    assert!(matches!(
+        vm.advance_clock(Duration::from_millis(1)),
+        Err(Error::RealClock)
+    ));
+}
+
+#[test]
+fn a_failing_fiber_does_not_drop_the_others() {
+    let mut vm = timer_vm();
+    vm.interpret(
+        "main",
+        "import \"scheduler\" for Scheduler
+import \"timer\" for Timer
+Scheduler.add {
+  Timer.sleep(10)
+  Fiber.abort(\"boom\")
+}
+Scheduler.add {
+  Timer.sleep(10)
+  System.print(\"still woken\")
+}
+Timer.sleep(20)",
+    )
+    .unwrap();
+    assert_eq!(vm.pending_timers(), 3);
+    let result = vm.advance_clock(Duration::from_millis(10));
+    assert!(matches!(result, Err(Error::Runtime { message, .. }) if message == "boom"));
+    assert_eq!(vm.take_output(), "still woken\n");
+    assert_eq!(vm.pending_timers(), 1);
+}
+
+#[test]
+fn errors_do_not_stop_the_clock() {
+    let mut vm = timer_vm();
+    vm.interpret(
+        "main",
+        "import \"scheduler\" for Scheduler
+import \"timer\" for Timer
+Scheduler.add {
+  Timer.sleep(10)
+  Fiber.abort(\"boom\")
+}
+Scheduler.add {
+  Timer.sleep(30)
+  System.print(\"later\")
+}
+Timer.sleep(50)",
+    )
+    .unwrap();
+    let result = vm.advance_clock(Duration::from_millis(40));
+    assert!(matches!(result, Err(Error::Runtime { message, .. }) if message == "boom"));
+    assert_eq!(vm.take_output(), "later\n");
+    assert_eq!(vm.next_timer(), Some(Duration::from_millis(10)));
+
+    let mut vm = timer_vm();
+    vm.interpret(
+        "main",
+        "import \"scheduler\" for Scheduler
+import \"timer\" for Timer
+Scheduler.add {
+  Timer.sleep(10)
+  Fiber.abort(\"boom\")
+}
+Timer.sleep(20)
+System.print(\"done\")",
+    )
+    .unwrap();
+    assert!(vm.run_timers().is_err());
+    assert_eq!(vm.take_output(), "done\n");
+    assert_eq!(vm.pending_timers(), 0);
+}
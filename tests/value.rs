@@ -0,0 +1,124 @@
+use wren_rs::{Configuration, Error, Type, Value, VM};
+
+#[test]
+fn unhashable_map_keys_are_errors() {
+    let mut vm = VM::new(Configuration::new());
+    vm.ensure_slots(1);
+    vm.set_slot_double(0, 1.0);
+    let value = Value::List(vec![Value::Map(vec![(
+        Value::List(vec![]),
+        Value::Num(1.0),
+    )])]);
+    match vm.set_value(0, &value) {
+        Err(Error::MapKey { found }) => assert_eq!(found, Type::List),
+        other => panic!("expected a MapKey error, got {:?}", other),
+    }
+    // Nothing was written.
+    assert_eq!(vm.get_slot_double(0), Some(1.0));
+}
+
+#[test]
+fn values_round_trip() {
+    let mut vm = VM::new(Configuration::new());
+    vm.ensure_slots(1);
+    let value = Value::Map(vec![
+        (
+            Value::String(b"list".to_vec()),
+            Value::List(vec![Value::Null, Value::Bool(true)]),
+        ),
+        (Value::Num(2.0), Value::String(vec![0, 255])),
+    ]);
+    vm.set_value(0, &value).unwrap();
+    assert_eq!(vm.get_value(0).unwrap(), value);
+}
+
+#[test]
+fn handles_in_values_outlive_the_vm() {
+    let mut vm = VM::new(Configuration::new());
+    vm.interpret("main", "class A {}\nvar a = A").unwrap();
+    vm.ensure_slots(1);
+    vm.get_variable("main", "a", 0).unwrap();
+    let first = vm.get_value(0).unwrap();
+    let second = vm.get_value(0).unwrap();
+    drop(vm);
+    assert_eq!(first, second);
+    assert!(format!("{:?}", first).starts_with("Object(Handle("));
+}
+
+#[test]
+fn missing_variables_are_errors() {
+    let mut vm = VM::new(Configuration::new());
+    vm.interpret("main", "var present = 1").unwrap();
+    assert!(vm.has_variablle("main", "present"));
+    assert!(!vm.has_variablle("nowhere", "present"));
+    assert!(matches!(
+        vm.get_variable("main", "absent", 0),
+        Err(Error::Variable { .. })
+    ));
+    assert!(matches!(
+        vm.get_variable("nowhere", "present", 0),
+        Err(Error::Variable { .. })
+    ));
+    vm.get_variable("main", "present", 3).unwrap();
+    assert_eq!(vm.get_slot_double(3), Some(1.0));
+}
+
+#[test]
+fn strings_are_read_byte_exact() {
+    let mut vm = VM::new(Configuration::new());
+    vm.ensure_slots(1);
+    vm.set_slot_bytes(0, b"\xffnul\0");
+    assert_eq!(vm.get_slot_string(0).unwrap(), b"\xffnul\0");
+    assert!(matches!(vm.get_slot_str(0), Err(Error::Utf8(_))));
+    vm.set_slot_double(0, 1.0);
+    assert!(matches!(vm.get_slot_string(0), Err(Error::Type { .. })));
+}
+
+#[test]
+fn conversions_leave_the_slot_count_alone() {
+    let mut vm = VM::new(Configuration::new());
+    vm.ensure_slots(2);
+    vm.set(0, vec![vec![1.0, 2.0], vec![3.0]]);
+    assert_eq!(vm.get_slot_count(), 2);
+    let value = vm.get_value(0).unwrap();
+    vm.set_value(1, &value).unwrap();
+    let lists: Vec<Vec<f64>> = vm.get(1).unwrap();
+    assert_eq!(lists, vec![vec![1.0, 2.0], vec![3.0]]);
+    assert_eq!(vm.get_slot_count(), 2);
+}
+
+#[test]
+fn typed_access_checks_the_slot() {
+    let mut vm = VM::new(Configuration::new());
+    vm.ensure_slots(1);
+    assert!(matches!(
+        vm.get::<f64>(1),
+        Err(Error::Slot { slot: 1, count: 1 })
+    ));
+    assert!(matches!(
+        vm.get::<f64>(-1),
+        Err(Error::Slot { slot: -1, .. })
+    ));
+    // Setting grows the slots as needed.
+    vm.set(3, "three");
+    assert_eq!(vm.get_slot_count(), 4);
+    assert_eq!(vm.get::<String>(3).unwrap(), "three");
+}
+
+#[test]
+fn handles_are_equal_when_they_share_the_object() {
+    let mut vm = VM::new(Configuration::new());
+    vm.interpret(
+        "main",
+        "class Thing {\n  construct new() {}\n}\nvar a = Thing.new()\nvar b = Thing.new()",
+    )
+    .unwrap();
+    vm.ensure_slots(1);
+    let mut get = |name| {
+        vm.get_variable("main", name, 0).unwrap();
+        vm.get_value(0).unwrap()
+    };
+    let (a, again, b) = (get("a"), get("a"), get("b"));
+    assert_eq!(a, again);
+    assert_ne!(a, b);
+}
@@ -1,34 +1,285 @@
 use std::env;
+use std::fs;
 use std::path::{Path, PathBuf};
-use std::process::Command;
 
-// #[allow(dead_code)]
-// fn make_debug(dir: &Path) {
-//     let status = Command::new("make").current_dir(dir).arg("debug").status();
-//     assert!(status.unwrap().success());
-//     println!("cargo:rustc-link-lib=static=wrend");
-// }
+// Lets the embedder stop a running script. Every VM gets its own hook, polled
+// on every loop back-edge and method call, and a non-NULL message aborts the
+// current fiber with that message as a runtime error.
+const INTERRUPT_TYPE: &str = "typedef const char* (*WrenInterruptFn)(WrenVM* vm);\n\n";
+const INTERRUPT_FIELD: &str = "  WrenInterruptFn interruptFn;\n";
 
-fn make(dir: &Path) {
-    let status = Command::new("make").current_dir(dir).arg("wren").status();
-    assert!(status.unwrap().success());
-    println!("cargo:rustc-link-lib=static=wren");
+const INTERRUPT_PRELUDE: &str = r#"#include <stdbool.h>
+#include <stddef.h>
+
+#define WREN_CHECK_INTERRUPT()                                                \
+    do                                                                         \
+    {                                                                          \
+      if (vm->interruptFn != NULL)                                             \
+      {                                                                        \
+        const char* interruptMessage = vm->interruptFn(vm);                    \
+        if (interruptMessage != NULL)                                          \
+        {                                                                      \
+          fiber->error = wrenNewString(vm, interruptMessage);                  \
+          RUNTIME_ERROR();                                                     \
+        }                                                                      \
+      }                                                                        \
+    } while (false)
+
+"#;
+
+// wrenNewVM() zeroes the whole struct, so the hook starts out unset.
+const INTERRUPT_SETTER: &str = r#"
+void wrenSetInterruptFn(WrenVM* vm, WrenInterruptFn fn)
+{
+  vm->interruptFn = fn;
+}
+"#;
+
+// The slot API can only add slots. This drops the ones past `numSlots` again,
+// so scratch slots used by the bindings don't pile up.
+const TRUNCATE_SLOTS: &str = r#"
+void wrenTruncateSlots(WrenVM* vm, int numSlots)
+{
+  if (vm->apiStack != NULL && numSlots < wrenGetSlotCount(vm))
+  {
+    vm->fiber->stackTop = vm->apiStack + numSlots;
+  }
+}
+"#;
+
+// The slot API only tells apart the types in WrenType, fibers are among the
+// objects it calls unknown.
+const SLOT_IS_FIBER: &str = r#"
+bool wrenGetSlotIsFiber(WrenVM* vm, int slot)
+{
+  validateApiSlot(vm, slot);
+  return IS_FIBER(vm->apiStack[slot]);
+}
+"#;
+
+// What a handle refers to, without relying on how WrenHandle or Value are laid
+// out. Objects are told apart by address, anything else by what it holds.
+const HANDLE_IDENTITY: &str = r#"
+uint64_t wrenGetHandleIdentity(WrenHandle* handle)
+{
+  Value value = handle->value;
+  if (IS_OBJ(value)) return (uint64_t)(uintptr_t)AS_OBJ(value);
+  if (IS_NUM(value))
+  {
+    union { double num; uint64_t bits; } data;
+    data.num = AS_NUM(value);
+    return data.bits;
+  }
+  if (IS_BOOL(value)) return AS_BOOL(value) ? 1 : 2;
+  return 0;
+}
+"#;
+
+// Lines of wren_vm.c the check goes after.
+const INTERRUPT_POINTS: [&str; 2] = ["ip -= offset;", "completeCall:"];
+
+// Lets the reallocate function refuse an allocation. wren writes to whatever
+// it gets back without checking for NULL, so a refused allocation jumps back
+// to where the interpreter was entered instead, and the running fiber is
+// aborted with a runtime error.
+const MEMORY_INCLUDE: &str = "#include <setjmp.h>\n\n";
+const MEMORY_FIELDS: &str = "  jmp_buf* outOfMemory;\n  bool allocationMayFail;\n";
+
+// Only allocations made while the interpreter runs may fail. The compiler run
+// by wrenInterpret() and the slot API used by foreign methods can't recover.
+const MEMORY_REALLOCATE: &str = r#"static void* reallocateGuarded(WrenVM* vm, void* memory, size_t newSize)
+{
+  bool mayFail = newSize > 0 && vm->outOfMemory != NULL && vm->apiStack == NULL;
+  vm->allocationMayFail = mayFail;
+  void* result = vm->config.reallocateFn(memory, newSize, vm->config.userData);
+  if (result == NULL && mayFail)
+  {
+    // What is over the limit may well be garbage.
+    vm->allocationMayFail = false;
+    wrenCollectGarbage(vm);
+    vm->allocationMayFail = true;
+    result = vm->config.reallocateFn(memory, newSize, vm->config.userData);
+  }
+  vm->allocationMayFail = false;
+  if (result == NULL && mayFail) longjmp(*vm->outOfMemory, 1);
+  return result;
+}
+
+"#;
+
+const MEMORY_REALLOCATE_FN: &str =
+    "void* wrenReallocate(WrenVM* vm, void* memory, size_t oldSize, size_t newSize)";
+const MEMORY_RETURN: &str = "return vm->config.reallocateFn(memory, newSize, vm->config.userData);";
+const MEMORY_RETURN_GUARDED: &str = "return reallocateGuarded(vm, memory, newSize);";
+
+const INTERPRETER: &str = "static WrenInterpretResult runInterpreter(";
+const INTERPRETER_PROTOTYPE: &str =
+    "static WrenInterpretResult runInterpreter(WrenVM* vm, ObjFiber* fiber);\n\n";
+
+// Every allocation happens at a point where the collector may run, so the
+// heap is consistent whenever one is refused. What was half built is
+// unreachable and left to the collector.
+const MEMORY_GUARD: &str = r#"
+static WrenInterpretResult runInterpreter(WrenVM* vm, ObjFiber* fiber)
+{
+  jmp_buf outOfMemory;
+  jmp_buf* outer = vm->outOfMemory;
+  Compiler* compiler = vm->compiler;
+  int numTempRoots = vm->numTempRoots;
+  ObjFiber* volatile next = fiber;
+
+  vm->outOfMemory = &outOfMemory;
+  if (setjmp(outOfMemory) != 0)
+  {
+    vm->compiler = compiler;
+    vm->numTempRoots = numTempRoots;
+    vm->outOfMemory = NULL;
+    vm->fiber->error = wrenNewString(vm, "Out of memory.");
+    vm->outOfMemory = &outOfMemory;
+    runtimeError(vm);
+    if (vm->fiber == NULL)
+    {
+      vm->outOfMemory = outer;
+      return WREN_RESULT_RUNTIME_ERROR;
+    }
+    next = vm->fiber;
+  }
+  WrenInterpretResult result = runInterpreterUnguarded(vm, next);
+  vm->outOfMemory = outer;
+  return result;
+}
+
+bool wrenAllocationMayFail(WrenVM* vm)
+{
+  return vm->allocationMayFail;
+}
+"#;
+
+// Puts `text` right before or after the first line of `source` that is
+// `line`, ignoring indentation.
+fn insert(source: &str, line: &str, text: &str, after: bool) -> String {
+    let mut patched = String::new();
+    let mut found = false;
+    for current in source.lines() {
+        let matches = !found && current.trim() == line;
+        if matches && !after {
+            patched.push_str(text);
+        }
+        patched.push_str(current);
+        patched.push('\n');
+        if matches && after {
+            patched.push_str(text);
+        }
+        found |= matches;
+    }
+    assert!(found, "`{}` not found", line);
+    patched
+}
+
+fn patch_header(source: &str) -> String {
+    let source = insert(source, "struct WrenVM", INTERRUPT_TYPE, false);
+    let source = insert(&source, "struct WrenVM", MEMORY_INCLUDE, false);
+    let source = insert(&source, "WrenConfiguration config;", INTERRUPT_FIELD, true);
+    insert(&source, "WrenConfiguration config;", MEMORY_FIELDS, true)
+}
+
+fn patch_vm(source: &str) -> String {
+    let mut patched = String::from(INTERRUPT_PRELUDE);
+    let mut found = [false; 2];
+    let (mut reallocates, mut returns, mut interpreters) = (0, 0, 0);
+    for line in source.lines() {
+        let trimmed = line.trim();
+        if trimmed == MEMORY_REALLOCATE_FN {
+            patched.push_str(MEMORY_REALLOCATE);
+            reallocates += 1;
+        }
+        if trimmed == MEMORY_RETURN {
+            patched.push_str(&line.replace(MEMORY_RETURN, MEMORY_RETURN_GUARDED));
+            returns += 1;
+        } else if trimmed.starts_with(INTERPRETER) && !trimmed.ends_with(';') {
+            // The original becomes runInterpreterUnguarded() and every caller
+            // goes through the guard instead.
+            patched.push_str(INTERPRETER_PROTOTYPE);
+            patched.push_str(&line.replace("runInterpreter(", "runInterpreterUnguarded("));
+            interpreters += 1;
+        } else {
+            patched.push_str(line);
+        }
+        patched.push('\n');
+        if let Some(i) = INTERRUPT_POINTS.iter().position(|p| trimmed == *p) {
+            patched.push_str("      WREN_CHECK_INTERRUPT();\n");
+            found[i] = true;
+        }
+    }
+    for (point, found) in INTERRUPT_POINTS.iter().zip(found) {
+        assert!(found, "`{}` not found in wren_vm.c", point);
+    }
+    assert_eq!(reallocates, 1, "wrenReallocate() not found in wren_vm.c");
+    assert_eq!(
+        returns, 1,
+        "the end of wrenReallocate() not found in wren_vm.c"
+    );
+    assert_eq!(interpreters, 1, "runInterpreter() not found in wren_vm.c");
+    patched.push_str(MEMORY_GUARD);
+    patched.push_str(INTERRUPT_SETTER);
+    patched.push_str(TRUNCATE_SLOTS);
+    patched.push_str(SLOT_IS_FIBER);
+    patched.push_str(HANDLE_IDENTITY);
+    patched
+}
+
+// The VM sources are built from a patched copy, so that all of them see the
+// extra fields in WrenVM.
+fn copy_vm(src: &Path, out: &Path) -> Vec<PathBuf> {
+    let vm_dir = out.join("vm");
+    fs::create_dir_all(&vm_dir).unwrap();
+    let mut sources = Vec::new();
+    for entry in fs::read_dir(src.join("vm")).unwrap() {
+        let path = entry.unwrap().path();
+        let name = path.file_name().unwrap().to_str().unwrap().to_string();
+        let mut content = fs::read_to_string(&path).unwrap();
+        if name == "wren_vm.h" {
+            content = patch_header(&content);
+        } else if name == "wren_vm.c" {
+            content = patch_vm(&content);
+        }
+        let target = vm_dir.join(&name);
+        fs::write(&target, content).unwrap();
+        if name.ends_with(".c") {
+            sources.push(target);
+        }
+    }
+    sources
 }
 
 fn main() {
     let manifest_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
-    let manifest_path = Path::new(&manifest_dir);
-    let wren_make_dir: PathBuf;
-    if cfg!(target_os = "macos") {
-        wren_make_dir = manifest_path.join("wren/projects/make.mac");
-    } else if cfg!(target_os = "linux") {
-        wren_make_dir = manifest_path.join("wren/projects/make");
-    } else {
-        wren_make_dir = manifest_path.join("wren/projects/make");
-    }
-    let wren_lib_dir = manifest_path.join("wren/lib");
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    let src = Path::new(&manifest_dir).join("wren/src");
 
-    make(&wren_make_dir);
+    let mut build = cc::Build::new();
+    build
+        .include(src.join("include"))
+        .include(out_dir.join("vm"))
+        .include(src.join("optional"))
+        .warnings(false);
+    // wren_common.h turns both optional modules on unless told otherwise.
+    for (feature, define) in [("META", "WREN_OPT_META"), ("RANDOM", "WREN_OPT_RANDOM")] {
+        let enabled = env::var_os(format!("CARGO_FEATURE_{}", feature)).is_some();
+        build.define(define, if enabled { "1" } else { "0" });
+    }
+    build.files(copy_vm(&src, &out_dir));
+    for entry in fs::read_dir(src.join("optional")).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_some_and(|ext| ext == "c") {
+            build.file(path);
+        }
+    }
+    build.compile("wren");
 
-    println!("cargo:rustc-link-search=native={}", wren_lib_dir.display());
+    if !cfg!(target_os = "windows") {
+        println!("cargo:rustc-link-lib=m");
+    }
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=wren/src");
 }
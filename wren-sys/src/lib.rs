@@ -48,6 +48,7 @@ pub type WrenBindForeignMethodFn = Option<
         signature: *const c_char,
     ) -> WrenForeignMethodFn,
 >;
+pub type WrenInterruptFn = Option<unsafe extern "C" fn(vm: *mut WrenVM) -> *const c_char>;
 pub type WrenWriteFn = Option<unsafe extern "C" fn(vm: *mut WrenVM, text: *const c_char)>;
 
 #[derive(Copy, Clone, PartialEq, Debug)]
@@ -184,4 +185,11 @@ extern "C" {
     pub fn wrenAbortFiber(vm: *mut WrenVM, slot: c_int);
     pub fn wrenGetUserData(vm: *mut WrenVM) -> *mut c_void;
     pub fn wrenSetUserData(vm: *mut WrenVM, user_data: *mut c_void);
+
+    // Not part of wren itself, patched into wren_vm.c by the build script.
+    pub fn wrenAllocationMayFail(vm: *mut WrenVM) -> bool;
+    pub fn wrenSetInterruptFn(vm: *mut WrenVM, f: WrenInterruptFn);
+    pub fn wrenTruncateSlots(vm: *mut WrenVM, num_slots: c_int);
+    pub fn wrenGetSlotIsFiber(vm: *mut WrenVM, slot: c_int) -> bool;
+    pub fn wrenGetHandleIdentity(handle: *mut WrenHandle) -> u64;
 }